@@ -0,0 +1,110 @@
+//! Compares allocating a new frame every generation with reusing two buffers,
+//! recomputing every square with only recomputing those near changes, and
+//! evaluating rules directly with looking them up in compiled tables.
+//! Run with `cargo bench`
+
+extern crate simulation;
+
+use std::time::{Duration, Instant};
+
+use simulation::{Frame, Rule, Simulation, Square};
+use simulation::compiled::Compiled;
+use simulation::game_of_life::{GameOfLife, Isotropic, State};
+
+const GENERATIONS: usize = 100;
+
+/// A frame with an irregular pattern of alive squares, the same one as the
+/// library's test fixture, which benches can't reach
+fn scattered(side: usize) -> Frame<State> {
+    let mut frame = Frame::new(side, side);
+    for y in 0..side {
+        for x in 0..side {
+            if (x * 7 + y * 13 + x * y) % 5 < 2 {
+                *frame.get_mut(x, y) = State::Alive;
+            }
+        }
+    }
+    frame
+}
+
+/// Run f a few times and return the fastest time
+fn time<F>(mut f: F) -> Duration
+where F: FnMut() {
+    (0..5).map(|_| {
+        let start = Instant::now();
+        f();
+        start.elapsed()
+    }).min().unwrap()
+}
+
+/// Time advancing a frame with next_frame and with a Simulation
+fn compare<T, F>(name: &str, frame: &Frame<T>, step: F)
+where T: Clone + Default + PartialEq, F: Fn(Square<T>) -> T {
+    let next_frame = time(|| {
+        let mut frame = frame.clone();
+        for _ in 0..GENERATIONS {
+            frame = frame.next_frame(&step);
+        }
+    });
+    let simulation = time(|| {
+        let mut sim = Simulation::new(frame.clone());
+        for _ in 0..GENERATIONS {
+            sim.step(&step);
+        }
+    });
+    println!("{}, {} generations of a {}x{} frame", name, GENERATIONS,
+             frame.width(), frame.height());
+    println!("    next_frame: {:?}", next_frame);
+    println!("    Simulation: {:?}", simulation);
+}
+
+fn main() {
+    // steps that do almost no work show the cost of the allocations, which
+    // grows with the size of the cells
+    compare("copy", &scattered(1024), |sq| { *sq.get(0, 0) });
+    compare("copy 256 byte cells", &Frame::<[u64; 32]>::new(256, 256),
+            |sq| { *sq.get(0, 0) });
+    compare("Game of Life", &scattered(256), |sq| { GameOfLife.step(sq) });
+
+    // once the pattern settles most of the frame is still, so only the
+    // squares near the changes need to be recomputed
+    let mut settled = Simulation::new(scattered(256));
+    for _ in 0..1000 {
+        settled.advance(&GameOfLife);
+    }
+    let full = time(|| {
+        let mut sim = settled.clone();
+        for _ in 0..GENERATIONS {
+            sim.advance(&GameOfLife);
+        }
+    });
+    let tracked = time(|| {
+        let mut sim = settled.clone();
+        for _ in 0..GENERATIONS {
+            sim.advance_tracked(&GameOfLife);
+        }
+    });
+    println!("settled Game of Life, {} generations of a 256x256 frame",
+             GENERATIONS);
+    println!("    advance:         {:?}", full);
+    println!("    advance_tracked: {:?}", tracked);
+
+    let isotropic = "B2-a/S12".parse::<Isotropic>().unwrap();
+    let compiled = Compiled::new(&isotropic).unwrap();
+    let direct = time(|| {
+        let mut frame = scattered(256);
+        for _ in 0..GENERATIONS {
+            frame = frame.advance(&isotropic);
+        }
+    });
+    let table = time(|| {
+        let mut frame = scattered(256);
+        for _ in 0..GENERATIONS {
+            frame = compiled.next_frame(&frame);
+        }
+    });
+    println!("{}, {} generations of a 256x256 frame", isotropic.name(),
+             GENERATIONS);
+    println!("    advance:             {:?}", direct);
+    println!("    Compiled next_frame: {:?}", table);
+}
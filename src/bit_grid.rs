@@ -0,0 +1,313 @@
+use super::{Boundary, Frame, Rule, HEXAGONAL};
+use super::game_of_life::{LifeLike, State};
+
+/// A frame of a two state simulation that packs 64 squares into each word so
+/// that Life-like rules can advance 64 squares at once with bitwise operations.
+/// Advancing a frame without masks with a LifeLike rule goes through a grid
+#[derive(Clone, Debug, PartialEq)]
+pub struct BitGrid {
+    /// each row starts on a new word, bit x % 64 of word x / 64 is square x
+    words: Vec<u64>,
+    width: usize,
+    height: usize,
+    /// the number of words in each row
+    stride: usize,
+    boundary: Boundary<State>,
+}
+
+impl BitGrid {
+    /// Creates an empty grid
+    pub fn new(width: usize, height: usize) -> BitGrid {
+        let stride = width.div_ceil(64);
+        BitGrid {
+            words: vec![0; stride * height],
+            width,
+            height,
+            stride,
+            boundary: Boundary::default(),
+        }
+    }
+
+    /// the width of the grid
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    /// the height of the grid
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    /// the state at (x, y)
+    pub fn get(&self, x: usize, y: usize) -> State {
+        if self.words[y * self.stride + x / 64] & (1 << (x % 64)) != 0 {
+            State::Alive
+        } else {
+            State::Dead
+        }
+    }
+
+    /// change the state at (x, y)
+    pub fn set(&mut self, x: usize, y: usize, state: State) {
+        let word = &mut self.words[y * self.stride + x / 64];
+        match state {
+            State::Alive => *word |= 1 << (x % 64),
+            State::Dead => *word &= !(1 << (x % 64)),
+        }
+    }
+
+    /// the boundary condition of the grid
+    pub fn boundary(&self) -> &Boundary<State> {
+        &self.boundary
+    }
+
+    /// the grid with its boundary condition replaced
+    pub fn with_boundary(mut self, boundary: Boundary<State>) -> BitGrid {
+        self.boundary = boundary;
+        self
+    }
+
+    /// the number of alive squares
+    pub fn population(&self) -> usize {
+        self.words.iter().map(|w| { w.count_ones() as usize }).sum()
+    }
+
+    /// the state at (x, y) where (x, y) may lie outside of the grid, in which
+    /// case the boundary condition decides what is there
+    fn at(&self, x: isize, y: isize) -> State {
+        match self.boundary.resolve(x, y, self.width, self.height) {
+            Some((x, y)) => self.get(x, y),
+            None => match self.boundary {
+                Boundary::Constant(state) => state,
+                _ => unreachable!(),
+            },
+        }
+    }
+
+    /// The words of row y, which may lie outside of the grid, shifted so that
+    /// bit x of the result holds the square at x + dx for dx in -1, 0 and 1
+    fn shifted_rows(&self, y: isize) -> [Vec<u64>; 3] {
+        // the boundary maps squares inside of the grid horizontally to the
+        // same column, so only the row and the two ends need to be looked up
+        let row = match self.boundary.resolve(0, y, self.width, self.height) {
+            Some((_, y)) => {
+                self.words[y * self.stride..(y + 1) * self.stride].to_vec()
+            },
+            None => {
+                let fill = if self.at(0, y) == State::Alive { !0 } else { 0 };
+                let mut row = vec![fill; self.stride];
+                self.clear_padding(&mut row);
+                row
+            },
+        };
+        let left = (self.at(-1, y) == State::Alive) as u64;
+        let right = (self.at(self.width as isize, y) == State::Alive) as u64;
+
+        // bit x of west is the square at x - 1
+        let mut west = vec![0; self.stride];
+        let mut carry = left;
+        for (i, &word) in row.iter().enumerate() {
+            west[i] = word << 1 | carry;
+            carry = word >> 63;
+        }
+        self.clear_padding(&mut west);
+
+        // bit x of east is the square at x + 1
+        let mut east = vec![0; self.stride];
+        let last = self.width - 1;
+        for i in 0..self.stride {
+            let next = row.get(i + 1).map_or(0, |w| { w << 63 });
+            east[i] = row[i] >> 1 | next;
+        }
+        east[last / 64] |= right << (last % 64);
+        self.clear_padding(&mut east);
+
+        [west, row, east]
+    }
+
+    /// Zero the bits past the width of the grid in the last word of a row
+    fn clear_padding(&self, row: &mut [u64]) {
+        let used = self.width % 64;
+        if used != 0 {
+            row[self.stride - 1] &= (1 << used) - 1;
+        }
+    }
+
+    /// return the next grid of the simulation advancing it with a Life-like
+    /// rule on a square or hexagonal lattice. This computes the same result as
+    /// advancing a Frame with the rule
+    pub fn next_frame(&self, rule: &LifeLike) -> BitGrid {
+        assert!(!rule.neighborhood().is_triangular(),
+                "a bit grid can't advance rules on a triangular lattice");
+        // hexagons don't neighbor the squares at (-1, -1) and (1, 1)
+        let hex = rule.neighborhood() == HEXAGONAL;
+        // the neighbor counts that cause birth and survival
+        let birth = (0..9).filter(|&n| { rule.is_birth(n) }).collect::<Vec<_>>();
+        let survival = (0..9).filter(|&n| { rule.is_survival(n) })
+            .collect::<Vec<_>>();
+
+        let mut next = BitGrid::new(self.width, self.height)
+            .with_boundary(self.boundary.clone());
+        if self.width == 0 || self.height == 0 {
+            // there are no squares to shift
+            return next;
+        }
+        let mut above = self.shifted_rows(-1);
+        let mut middle = self.shifted_rows(0);
+        for y in 0..self.height {
+            let below = self.shifted_rows(y as isize + 1);
+
+            for i in 0..self.stride {
+                let neighbors = [
+                    if hex { 0 } else { above[0][i] }, above[1][i], above[2][i],
+                    middle[0][i], middle[2][i],
+                    below[0][i], below[1][i], if hex { 0 } else { below[2][i] },
+                ];
+                // add the neighbors up 64 squares at a time, where bit x of
+                // counts[k] is bit k of the count of square x
+                let mut counts = [0u64; 4];
+                for &neighbor in neighbors.iter() {
+                    let mut carry = neighbor;
+                    for count in counts.iter_mut() {
+                        let overflow = *count & carry;
+                        *count ^= carry;
+                        carry = overflow;
+                    }
+                }
+                let equals = |n: usize| {
+                    counts.iter().enumerate().fold(!0, |acc, (k, &c)| {
+                        acc & if n & (1 << k) != 0 { c } else { !c }
+                    })
+                };
+
+                let alive = middle[1][i];
+                let born = birth.iter().fold(0, |acc, &n| { acc | equals(n) });
+                let stays = survival.iter().fold(0, |acc, &n| { acc | equals(n) });
+                next.words[y * self.stride + i] = (!alive & born) | (alive & stays);
+            }
+            let start = y * self.stride;
+            let row = &mut next.words[start..start + self.stride];
+            self.clear_padding(row);
+
+            above = middle;
+            middle = below;
+        }
+        next
+    }
+}
+
+/// Every boundary condition carries over, but a grid has no masks, so the
+/// frame must not have any
+impl<'a> From<&'a Frame<State>> for BitGrid {
+    fn from(frame: &'a Frame<State>) -> BitGrid {
+        assert!(!frame.is_masked(), "a bit grid can't hold masked squares");
+        let mut grid = BitGrid::new(frame.width(), frame.height())
+            .with_boundary(frame.boundary().clone());
+        for (x, y, &state) in frame.enumerate_squares() {
+            grid.set(x, y, state);
+        }
+        grid
+    }
+}
+
+impl<'a> From<&'a BitGrid> for Frame<State> {
+    fn from(grid: &'a BitGrid) -> Frame<State> {
+        let mut frame = Frame::new(grid.width(), grid.height())
+            .with_boundary(grid.boundary().clone());
+        for y in 0..grid.height() {
+            for x in 0..grid.width() {
+                *frame.get_mut(x, y) = grid.get(x, y);
+            }
+        }
+        frame
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::{Boundary, Frame, Mask, Rule, Simulation};
+    use super::super::frame::tests::scattered;
+    use super::super::game_of_life::{rule, GameOfLife, LifeLike};
+    use super::super::game_of_life::State::{Alive, Dead};
+    use super::BitGrid;
+
+    #[test]
+    fn conversion() {
+        let frame = scattered(70, 5, Boundary::Reflecting);
+        let grid = BitGrid::from(&frame);
+        assert_eq!(grid.get(0, 0), Alive);
+        assert_eq!(grid.get(1, 0), Dead);
+        assert_eq!(grid.population(),
+                   frame.enumerate_squares().filter(|&(_, _, s)| { *s == Alive })
+                       .count());
+        assert_eq!(Frame::from(&grid), frame);
+    }
+
+    #[test]
+    #[should_panic(expected = "masked")]
+    fn masked() {
+        let mut frame = scattered(8, 8, Boundary::Toroidal);
+        frame.set_mask(3, 3, Mask::Wall);
+        let _ = BitGrid::from(&frame);
+    }
+
+    #[test]
+    fn matches_rule() {
+        let frame = scattered(11, 9, Boundary::Toroidal);
+        let mut grid = BitGrid::from(&frame);
+        let mut expected = frame;
+        for _ in 0..10 {
+            grid = grid.next_frame(&LifeLike::conway());
+            expected = expected.next_frame(rule);
+            assert_eq!(Frame::from(&grid), expected);
+        }
+    }
+
+    #[test]
+    fn matches_rules_across_words() {
+        let rules = ["B3/S23", "B36/S23", "B2/S", "B0/S8", "B3678/S34678",
+                     "B2/S34H", "B0/S24H"];
+        let boundaries = [
+            Boundary::Toroidal,
+            Boundary::Constant(Dead),
+            Boundary::Constant(Alive),
+            Boundary::Reflecting,
+            Boundary::KleinBottle,
+            Boundary::Helical(3),
+        ];
+        for rulestring in rules.iter() {
+            let life_like = rulestring.parse::<LifeLike>().unwrap();
+            for boundary in boundaries.iter() {
+                for &width in [64, 65, 130].iter() {
+                    let frame = scattered(width, 6, boundary.clone());
+                    let grid = BitGrid::from(&frame).next_frame(&life_like);
+                    assert_eq!(Frame::from(&grid),
+                               frame.next_frame(|sq| { life_like.step(sq) }));
+                }
+                for &(w, h) in [(0, 0), (0, 5), (5, 0)].iter() {
+                    let empty = scattered(w, h, boundary.clone());
+                    let grid = BitGrid::from(&empty).next_frame(&life_like);
+                    assert_eq!(Frame::from(&grid), empty);
+                }
+            }
+        }
+    }
+    #[test]
+    fn behind_rule() {
+        let frame = scattered(70, 9, Boundary::Helical(2));
+        let grid = BitGrid::from(&frame).next_frame(&LifeLike::conway());
+        assert_eq!(frame.advance(&GameOfLife), Frame::from(&grid));
+        let mut sim = Simulation::new(frame.clone());
+        sim.advance(&LifeLike::conway());
+        assert_eq!(*sim.frame(), Frame::from(&grid));
+
+        // masked frames and triangles are advanced square by square
+        let mut masked = frame.clone();
+        masked.set_mask(3, 3, Mask::Emitter(Alive));
+        assert_eq!(masked.advance(&GameOfLife), masked.next_frame(rule));
+        let triangular = "B4/S34T".parse::<LifeLike>().unwrap();
+        assert_eq!(triangular.advance_frame(&frame), None);
+        assert_eq!(frame.advance(&triangular),
+                   frame.next_frame(|sq| { triangular.step(sq) }));
+    }
+}
@@ -0,0 +1,316 @@
+use super::{FiniteState, Frame, Neighborhood, Rule, Square};
+
+use std::collections::HashMap;
+use std::error::Error;
+use std::fmt;
+use std::sync::RwLock;
+
+/// The largest number of configurations that are enumerated when a rule is
+/// compiled, rules with more configurations are memoized as they are seen
+const MAX_ENUMERATED: u128 = 1 << 20;
+
+/// A deterministic rule over a finite set of states compiled into a table from
+/// each configuration of a square and its neighbors to the square's next state
+pub struct Compiled<R>
+where R: Rule {
+    rule: R,
+    /// the square itself followed by the offsets of its neighbors
+    offsets: Vec<(isize, isize)>,
+    table: Table<R::State>,
+}
+
+/// The next states of configurations, which are numbered by reading the index
+/// of each square's state as a digit, with the square itself as the lowest
+enum Table<T> {
+    /// the next state of every configuration
+    Enumerated(Vec<T>),
+    /// the next states of the configurations that have been seen so far
+    Memoized(RwLock<HashMap<u128, T>>),
+}
+
+/// An error describing why a rule couldn't be compiled
+#[derive(Clone, Debug, PartialEq)]
+pub enum CompileError {
+    /// The rule doesn't always compute the same state for the same
+    /// surroundings, so there is no table to compile it into
+    Nondeterministic,
+    /// The rule computes different states for the same surroundings at
+    /// different coordinates, like rules on a triangular lattice do
+    Nonuniform,
+    /// The rule computes different states for the same surroundings in
+    /// different generations
+    Nonstationary,
+    /// The neighborhood has too many configurations to number them
+    TooManyConfigurations,
+}
+
+impl fmt::Display for CompileError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            CompileError::Nondeterministic =>
+                write!(f, "nondeterministic rules can't be compiled"),
+            CompileError::Nonuniform =>
+                write!(f, "rules that vary by coordinate can't be compiled"),
+            CompileError::Nonstationary =>
+                write!(f, "rules that change over time can't be compiled"),
+            CompileError::TooManyConfigurations =>
+                write!(f, "the neighborhood has too many configurations"),
+        }
+    }
+}
+
+impl Error for CompileError {}
+
+impl<R> Compiled<R>
+where R: Rule,
+      R::State: FiniteState + Default + Clone {
+    /// Compile a rule, enumerating every configuration up front if there
+    /// aren't too many of them
+    pub fn new(rule: R) -> Result<Compiled<R>, CompileError> {
+        if !rule.is_deterministic() {
+            return Err(CompileError::Nondeterministic);
+        }
+        // triangles that point up and down see their neighbors at different
+        // offsets, so the same offsets hold different surroundings
+        if !rule.is_uniform() || rule.neighborhood().is_triangular() {
+            return Err(CompileError::Nonuniform);
+        }
+        if !rule.is_stationary() {
+            return Err(CompileError::Nonstationary);
+        }
+
+        let mut offsets = vec![(0, 0)];
+        offsets.extend(rule.neighborhood().offsets()
+                       .filter(|&o| { o != (0, 0) }));
+        let states = R::State::count() as u128;
+        let configurations = offsets.iter()
+            .try_fold(1u128, |acc, _| { acc.checked_mul(states) })
+            .ok_or(CompileError::TooManyConfigurations)?;
+
+        let mut compiled = Compiled {
+            rule,
+            offsets,
+            table: Table::Memoized(RwLock::new(HashMap::new())),
+        };
+        if configurations <= MAX_ENUMERATED {
+            let table = (0..configurations)
+                .map(|key| { compiled.evaluate(key) })
+                .collect();
+            compiled.table = Table::Enumerated(table);
+        }
+        Ok(compiled)
+    }
+
+    /// the rule that was compiled
+    pub fn rule(&self) -> &R {
+        &self.rule
+    }
+
+    /// Whether every configuration was enumerated when the rule was compiled
+    pub fn is_enumerated(&self) -> bool {
+        match self.table {
+            Table::Enumerated(_) => true,
+            Table::Memoized(_) => false,
+        }
+    }
+
+    /// Compute the next state of a configuration with the rule, by laying it
+    /// out in a frame just large enough to hold the neighborhood
+    fn evaluate(&self, key: u128) -> R::State {
+        let r = self.radius();
+        let states = R::State::count() as u128;
+        let mut frame = Frame::new_finite(2 * r + 1, 2 * r + 1);
+        let mut key = key;
+        for &(i, j) in self.offsets.iter() {
+            let (x, y) = (r as isize + i, r as isize + j);
+            let state = R::State::from_index((key % states) as usize);
+            *frame.get_mut(x as usize, y as usize) = state;
+            key /= states;
+        }
+        self.rule.step(frame.square(r, r))
+    }
+
+    /// The number of a configuration given the index of the state at each of
+    /// the offsets
+    fn key<F>(&self, index: F) -> u128
+    where F: Fn(isize, isize) -> usize {
+        let states = R::State::count() as u128;
+        self.offsets.iter().rev().fold(0, |key, &(i, j)| {
+            key * states + index(i, j) as u128
+        })
+    }
+
+    /// The next state of a configuration
+    fn lookup(&self, key: u128) -> R::State {
+        match self.table {
+            Table::Enumerated(ref table) => table[key as usize].clone(),
+            Table::Memoized(ref memo) => {
+                if let Some(state) = memo.read().unwrap().get(&key) {
+                    return state.clone();
+                }
+                let state = self.evaluate(key);
+                memo.write().unwrap().insert(key, state.clone());
+                state
+            },
+        }
+    }
+
+    /// return the next frame of the simulation advancing it with the compiled
+    /// rule. This computes the same result as advancing it with the rule, but
+    /// looks up the states of every square only once
+    pub fn next_frame(&self, frame: &Frame<R::State>) -> Frame<R::State> {
+        // the indices of the states of the frame padded by the radius of the
+        // neighborhood on every side according to its boundary condition
+        let r = self.radius();
+        let p = r as isize;
+        let (w, h) = (frame.width() + 2 * r, frame.height() + 2 * r);
+        let mut padded = Vec::with_capacity(w * h);
+        for y in 0..h {
+            for x in 0..w {
+                padded.push(frame.at(x as isize - p, y as isize - p).index());
+            }
+        }
+
+        frame.next_frame(|sq| {
+            // the square is at (x + r, y + r) in the padded indices
+            let (x, y) = sq.coordinate();
+            let (px, py) = ((x + r) as isize, (y + r) as isize);
+            self.lookup(self.key(|i, j| {
+                padded[(py + j) as usize * w + (px + i) as usize]
+            }))
+        })
+    }
+
+    /// The largest distance along either axis of any offset
+    fn radius(&self) -> usize {
+        self.offsets.iter()
+            .map(|&(i, j)| { i.unsigned_abs().max(j.unsigned_abs()) })
+            .max()
+            .unwrap_or(0)
+    }
+}
+
+impl<R> Rule for Compiled<R>
+where R: Rule,
+      R::State: FiniteState + Default + Clone {
+    type State = R::State;
+
+    fn name(&self) -> &str { self.rule.name() }
+    fn neighborhood(&self) -> Neighborhood { self.rule.neighborhood() }
+    fn state_count(&self) -> Option<usize> { self.rule.state_count() }
+
+    fn step(&self, curr: Square<R::State>) -> R::State {
+        self.lookup(self.key(|i, j| { curr.get(i, j).index() }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::{Boundary, FiniteState, Frame, Rule, Square, MOORE};
+    use super::super::frame::tests::pattern;
+    use super::super::game_of_life::{GameOfLife, Isotropic, LifeLike, State};
+    use super::super::generations::Generations;
+    use super::super::larger_than_life::LargerThanLife;
+    use super::super::rainbow_life::{self, RainbowLife};
+    use super::{CompileError, Compiled};
+
+    /// A frame with an irregular pattern of the first two states past the
+    /// default one
+    fn scattered<T>(w: usize, h: usize, boundary: Boundary<T>) -> Frame<T>
+    where T: FiniteState + Default + Clone {
+        let states = [T::from_index(1),
+                      T::from_index(1 + 1 % (T::count() - 1))];
+        pattern(w, h, boundary, &states)
+    }
+
+    /// Each square takes the color that most of its neighbors have, if more
+    /// than two of them do
+    struct Majority;
+
+    impl Rule for Majority {
+        type State = rainbow_life::State;
+
+        fn name(&self) -> &str { "majority" }
+
+        fn step(&self, curr: Square<rainbow_life::State>)
+            -> rainbow_life::State {
+            let mut counts = [0; 4];
+            for state in curr.neighbors(&MOORE) {
+                counts[state.index()] += 1;
+            }
+            (1..4).filter(|&i| { counts[i] > 2 })
+                .max_by_key(|&i| { counts[i] })
+                .map_or(*curr.get(0, 0), rainbow_life::State::from_index)
+        }
+    }
+
+    #[test]
+    fn life() {
+        let compiled = Compiled::new(GameOfLife).unwrap();
+        assert!(compiled.is_enumerated());
+        assert_eq!(compiled.name(), GameOfLife.name());
+        let boundaries = [
+            Boundary::Toroidal,
+            Boundary::Constant(State::Alive),
+            Boundary::Reflecting,
+            Boundary::Helical(2),
+        ];
+        for boundary in boundaries.iter() {
+            let mut frame = scattered(13, 10, boundary.clone());
+            for _ in 0..10 {
+                let next = frame.advance(&GameOfLife);
+                assert_eq!(compiled.next_frame(&frame), next);
+                assert_eq!(frame.advance(&compiled), next);
+                frame = next;
+            }
+        }
+    }
+
+    #[test]
+    fn isotropic() {
+        let rule = "B2-a/S12".parse::<Isotropic>().unwrap();
+        let compiled = Compiled::new(&rule).unwrap();
+        let mut frame = scattered(20, 20, Boundary::Toroidal);
+        for _ in 0..10 {
+            let next = frame.advance(&rule);
+            assert_eq!(compiled.next_frame(&frame), next);
+            frame = next;
+        }
+    }
+
+    #[test]
+    fn memoized() {
+        // Generations states have too many configurations to enumerate
+        let brain = Generations::named("Star Wars").unwrap();
+        let compiled = Compiled::new(&brain).unwrap();
+        assert!(!compiled.is_enumerated());
+        let mut frame = scattered(20, 16, Boundary::Toroidal);
+        for _ in 0..10 {
+            let next = frame.advance(&brain);
+            assert_eq!(compiled.next_frame(&frame), next);
+            assert_eq!(frame.advance_parallel(3, &compiled), next);
+            frame = next;
+        }
+    }
+
+    #[test]
+    fn more_states() {
+        let compiled = Compiled::new(Majority).unwrap();
+        assert!(compiled.is_enumerated());
+        let frame = scattered(16, 16, Boundary::Reflecting);
+        assert_eq!(compiled.next_frame(&frame), frame.advance(&Majority));
+    }
+
+    #[test]
+    fn errors() {
+        assert_eq!(Compiled::new(RainbowLife).err(),
+                   Some(CompileError::Nondeterministic));
+        let life = "B3/S23".parse::<LifeLike>().unwrap();
+        assert!(Compiled::new(life).is_ok());
+        let ltl = "R7,C0,M1,S1..2,B1..2,NM".parse::<LargerThanLife>().unwrap();
+        assert_eq!(Compiled::new(ltl).err(),
+                   Some(CompileError::TooManyConfigurations));
+        let tri = "B4/S34T".parse::<LifeLike>().unwrap();
+        assert_eq!(Compiled::new(tri).err(), Some(CompileError::Nonuniform));
+    }
+}
@@ -0,0 +1,254 @@
+use super::{Frame, Square};
+
+use rand::{self, Rng, SeedableRng, XorShiftRng};
+
+use std::collections::HashMap;
+
+/// What a rule can see besides the surroundings of a square: the seed of the
+/// run, the generation that is being advanced, statistics about the frame that
+/// is being advanced and parameters set by the user
+#[derive(Clone, Debug, PartialEq, Default)]
+pub struct Context {
+    seed: u64,
+    generation: u64,
+    stats: Stats,
+    params: HashMap<String, f64>,
+}
+
+/// Statistics about a whole frame
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Default)]
+pub struct Stats {
+    squares: usize,
+    population: usize,
+    changed: usize,
+}
+
+/// One step of the SplitMix64 generator, which scrambles the bits of x
+fn mix(x: u64) -> u64 {
+    let mut z = x.wrapping_add(0x9e37_79b9_7f4a_7c15);
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    z ^ (z >> 31)
+}
+
+impl Context {
+    /// A context for the first generation of a run with a seed
+    pub fn new(seed: u64) -> Context {
+        Context { seed, ..Context::default() }
+    }
+
+    /// A context for the first generation of a run with a random seed
+    pub fn random() -> Context {
+        Context::new(rand::thread_rng().gen())
+    }
+
+    /// the seed of the run
+    pub fn seed(&self) -> u64 {
+        self.seed
+    }
+
+    /// the generation that is being advanced
+    pub fn generation(&self) -> u64 {
+        self.generation
+    }
+
+    /// the context with its generation replaced
+    pub fn with_generation(mut self, generation: u64) -> Context {
+        self.generation = generation;
+        self
+    }
+
+    /// statistics about the frame that is being advanced
+    pub fn stats(&self) -> &Stats {
+        &self.stats
+    }
+
+    /// the context with its statistics replaced
+    pub fn with_stats(mut self, stats: Stats) -> Context {
+        self.stats = stats;
+        self
+    }
+
+    /// the value of a parameter, if it is set
+    pub fn param(&self, name: &str) -> Option<f64> {
+        self.params.get(name).cloned()
+    }
+
+    /// set the value of a parameter
+    pub fn set_param(&mut self, name: &str, value: f64) {
+        self.params.insert(name.to_string(), value);
+    }
+
+    /// the context with a parameter set
+    pub fn with_param(mut self, name: &str, value: f64) -> Context {
+        self.set_param(name, value);
+        self
+    }
+
+    /// Move on to the next generation, whose frame has the given statistics
+    pub fn advance(&mut self, stats: Stats) {
+        self.generation += 1;
+        self.stats = stats;
+    }
+
+    /// A random number generator for the square at (x, y). It only depends on
+    /// the seed, the generation and the coordinate, so squares get the same
+    /// numbers no matter which order or thread they are computed in
+    pub fn rng(&self, x: usize, y: usize) -> XorShiftRng {
+        self.rng_from(&[self.generation, x as u64, y as u64])
+    }
+
+    /// A random number generator for decisions about the whole generation,
+    /// like the order squares are updated in, which is independent of the
+    /// generators of the squares
+    pub fn generation_rng(&self) -> XorShiftRng {
+        self.rng_from(&[self.generation, u64::MAX, u64::MAX, 1])
+    }
+
+    /// A random number generator seeded by the seed of the run and some words
+    fn rng_from(&self, words: &[u64]) -> XorShiftRng {
+        let hash = words.iter().fold(mix(self.seed), |h, &n| { mix(h ^ n) });
+        let (a, b) = (hash, mix(hash));
+        let mut seed = [a as u32, (a >> 32) as u32, b as u32, (b >> 32) as u32];
+        if seed == [0; 4] {
+            // xorshift can't start from all zeros
+            seed[0] = 1;
+        }
+        XorShiftRng::from_seed(seed)
+    }
+}
+
+impl Stats {
+    /// Statistics from the number of squares in a frame, how many don't have
+    /// the default value and how many changed in the last generation
+    pub fn new(squares: usize, population: usize, changed: usize) -> Stats {
+        Stats { squares, population, changed }
+    }
+
+    /// the number of squares in the frame
+    pub fn squares(&self) -> usize {
+        self.squares
+    }
+
+    /// the number of squares that don't have the default value
+    pub fn population(&self) -> usize {
+        self.population
+    }
+
+    /// the number of squares that changed in the last generation
+    pub fn changed(&self) -> usize {
+        self.changed
+    }
+
+    /// the fraction of squares that don't have the default value
+    pub fn density(&self) -> f64 {
+        if self.squares == 0 {
+            0.0
+        } else {
+            self.population as f64 / self.squares as f64
+        }
+    }
+}
+
+impl<T> Frame<T>
+where T: Default + PartialEq {
+    /// Statistics about the frame, not knowing how it changed
+    pub fn stats(&self) -> Stats {
+        let default = T::default();
+        let population = self.enumerate_squares()
+            .filter(|&(_, _, value)| { *value != default })
+            .count();
+        Stats::new(self.width() * self.height(), population, 0)
+    }
+
+    /// Statistics about the frame, which was advanced from previous
+    pub fn stats_since(&self, previous: &Frame<T>) -> Stats {
+        let changed = self.enumerate_squares()
+            .filter(|&(x, y, value)| { value != previous.get(x, y) })
+            .count();
+        Stats { changed, ..self.stats() }
+    }
+}
+
+impl<T> Frame<T>
+where T: Clone {
+    /// return the next frame of the simulation advancing the simulation using
+    /// a step function that also sees a context
+    pub fn next_frame_with_context<F>(&self, ctx: &Context, step: F)
+        -> Frame<T>
+    where F: Fn(Square<T>, &Context) -> T {
+        self.next_frame(|sq| { step(sq, ctx) })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::{Frame, Square};
+    use super::super::game_of_life::{self, State};
+    use super::super::game_of_life::State::{Alive, Dead};
+    use super::{Context, Stats};
+    use rand::Rng;
+
+    #[test]
+    fn rng() {
+        let ctx = Context::new(5).with_generation(2);
+        let draw = |ctx: &Context, x, y| {
+            ctx.rng(x, y).gen_iter::<u32>().take(4).collect::<Vec<_>>()
+        };
+        assert_eq!(draw(&ctx, 3, 4), draw(&ctx, 3, 4));
+        assert!(draw(&ctx, 3, 4) != draw(&ctx, 4, 3));
+        let later = ctx.clone().with_generation(3);
+        assert!(draw(&ctx, 3, 4) != draw(&later, 3, 4));
+        assert!(draw(&ctx, 3, 4) != draw(&Context::new(6), 3, 4));
+    }
+
+    #[test]
+    fn params() {
+        let mut ctx = Context::new(0).with_param("temperature", 0.5);
+        assert_eq!(ctx.param("temperature"), Some(0.5));
+        assert_eq!(ctx.param("pressure"), None);
+        ctx.set_param("temperature", 0.25);
+        assert_eq!(ctx.param("temperature"), Some(0.25));
+    }
+
+    #[test]
+    fn stats() {
+        let mut frame = Frame::<State>::new(4, 5);
+        *frame.get_mut(1, 0) = Alive;
+        *frame.get_mut(1, 1) = Alive;
+        *frame.get_mut(1, 2) = Alive;
+        assert_eq!(frame.stats(), Stats::new(20, 3, 0));
+        assert_eq!(frame.stats().density(), 0.15);
+
+        let next = frame.next_frame(game_of_life::rule);
+        assert_eq!(next.stats_since(&frame), Stats::new(20, 3, 4));
+    }
+
+    #[test]
+    fn with_context() {
+        // squares are born while the population is below a target and die
+        // once it is reached
+        let grow = |sq: Square<State>, ctx: &Context| {
+            let target = ctx.param("target").unwrap() as usize;
+            let (x, y) = sq.coordinate();
+            if ctx.stats().population() >= target {
+                Dead
+            } else if y as u64 == ctx.generation() && x % 2 == 0 {
+                Alive
+            } else {
+                *sq.get(0, 0)
+            }
+        };
+
+        let mut ctx = Context::new(0).with_param("target", 6.0);
+        let mut frame = Frame::<State>::new(4, 4);
+        let mut populations = Vec::new();
+        for _ in 0..4 {
+            let next = frame.next_frame_with_context(&ctx, &grow);
+            ctx.advance(next.stats_since(&frame));
+            populations.push(ctx.stats().population());
+            frame = next;
+        }
+        assert_eq!(populations, vec![2, 4, 6, 0]);
+    }
+}
@@ -1,10 +1,38 @@
+use super::Neighborhood;
+
+use std::thread;
+
 /// Represents a frame of a simulation
 /// This internal representation is not stable and should not be relied upon
-#[derive(Debug, PartialEq)]
+#[derive(Clone, Debug)]
 pub struct Frame<T> {
     data: Vec<T>,
     width: usize,
     height: usize,
+    boundary: Boundary<T>,
+    /// the mask of every square, or nothing if no square has ever been masked
+    masks: Vec<Option<Mask<T>>>,
+}
+
+/// Determines what lies beyond the edges of a frame, i.e. what a square sees
+/// when it looks at a point that is outside of the frame
+#[derive(Clone, Debug, PartialEq, Default)]
+pub enum Boundary<T> {
+    /// Opposite edges are glued together, so the frame is a torus
+    #[default]
+    Toroidal,
+    /// Every point outside of the frame has a fixed value
+    Constant(T),
+    /// The edges act as mirrors, the point just past an edge is the point just
+    /// inside of it
+    Reflecting,
+    /// The top and bottom edges are glued together, the left and right edges
+    /// are glued together with a twist that flips the frame vertically
+    KleinBottle,
+    /// The top and bottom edges are glued together, the left and right edges
+    /// are glued together but shifted down by the given number of rows. A
+    /// shift of 1 turns the frame into a single helix of cells
+    Helical(isize),
 }
 
 impl<T> Frame<T>
@@ -12,7 +40,45 @@ where T: Default + Clone {
     /// Creates an empty frame
     pub fn new(x: usize, y: usize) -> Frame<T> {
         let data = vec![T::default(); x * y];
-        Frame::<T> {data: data, width: x, height: y}
+        Frame::<T> {
+            data,
+            width: x,
+            height: y,
+            boundary: Boundary::default(),
+            masks: Vec::new(),
+        }
+    }
+
+    /// Creates an empty frame whose cells outside of the frame are dead, i.e.
+    /// have the default value
+    pub fn new_finite(x: usize, y: usize) -> Frame<T> {
+        Frame::new(x, y).with_boundary(Boundary::Constant(T::default()))
+    }
+
+    /// mask the square at (x, y) so that rules never overwrite it. Absorbing
+    /// squares are emptied and emitters take on their state right away
+    pub fn set_mask(&mut self, x: usize, y: usize, mask: Mask<T>) {
+        match mask {
+            Mask::Wall => {},
+            Mask::Absorbing => *self.get_mut(x, y) = T::default(),
+            Mask::Emitter(ref value) => *self.get_mut(x, y) = value.clone(),
+        }
+        if self.masks.is_empty() {
+            self.masks = vec![None; self.width * self.height];
+        }
+        self.masks[y * self.width + x] = Some(mask);
+    }
+
+    /// mask every square in the rectangle of the given size whose top left
+    /// corner is at (x, y), e.g. to build walls and containers or to put
+    /// emitters along an edge
+    pub fn mask_rect(&mut self, x: usize, y: usize, width: usize,
+                     height: usize, mask: Mask<T>) {
+        for j in y..y + height {
+            for i in x..x + width {
+                self.set_mask(i, j, mask.clone());
+            }
+        }
     }
 }
 
@@ -30,15 +96,142 @@ impl<T> Frame<T> {
 
     /// the data at (x, y)
     pub fn get(&self, x: usize, y: usize) -> &T {
-        &self.data[y * self.height + x]
+        &self.data[y * self.width + x]
     }
 
     /// get a mutable reference to the data at (x, y)
     pub fn get_mut(&mut self, x: usize, y: usize) -> &mut T {
-        &mut self.data[y * self.height + x]
+        &mut self.data[y * self.width + x]
+    }
+
+    /// the square at (x, y), as seen by a step function
+    pub fn square(&self, x: usize, y: usize) -> Square<'_, T> {
+        Square {
+            frame: self,
+            point: (x, y),
+        }
+    }
+
+    /// the boundary condition of the frame
+    pub fn boundary(&self) -> &Boundary<T> {
+        &self.boundary
+    }
+
+    /// change the boundary condition of the frame
+    pub fn set_boundary(&mut self, boundary: Boundary<T>) {
+        self.boundary = boundary;
+    }
+
+    /// the frame with its boundary condition replaced
+    pub fn with_boundary(mut self, boundary: Boundary<T>) -> Frame<T> {
+        self.boundary = boundary;
+        self
+    }
+
+    /// the mask of the square at (x, y), if it has one
+    pub fn mask(&self, x: usize, y: usize) -> Option<&Mask<T>> {
+        self.masks.get(y * self.width + x).and_then(Option::as_ref)
+    }
+
+    /// whether any square of the frame has a mask
+    pub fn is_masked(&self) -> bool {
+        self.masks.iter().any(Option::is_some)
+    }
+
+    /// let rules overwrite the square at (x, y) again
+    pub fn clear_mask(&mut self, x: usize, y: usize) {
+        if let Some(mask) = self.masks.get_mut(y * self.width + x) {
+            *mask = None;
+        }
+    }
+
+    /// Map a point that may lie outside of the frame to the point inside of the
+    /// frame that it refers to. Returns None if the point is outside of the
+    /// frame and the boundary is constant, or if the frame has no squares
+    pub fn resolve(&self, x: isize, y: isize) -> Option<(usize, usize)> {
+        self.boundary.resolve(x, y, self.width, self.height)
+    }
+
+    /// the data at (x, y) where (x, y) may lie outside of the frame, in which
+    /// case the boundary condition decides what is there. Panics if the frame
+    /// has no squares and the boundary isn't constant
+    pub fn at(&self, x: isize, y: isize) -> &T {
+        match self.resolve(x, y) {
+            Some((x, y)) => self.get(x, y),
+            None => match self.boundary {
+                Boundary::Constant(ref value) => value,
+                _ => panic!("a {}x{} frame has no squares to wrap around to",
+                            self.width, self.height),
+            },
+        }
     }
 }
 
+/// Frames are equal if their squares, boundaries and masks are, however the
+/// masks are stored
+impl<T> PartialEq for Frame<T>
+where T: PartialEq {
+    fn eq(&self, other: &Frame<T>) -> bool {
+        self.width == other.width && self.height == other.height
+            && self.data == other.data && self.boundary == other.boundary
+            && (0..self.height).all(|y| {
+                (0..self.width).all(|x| { self.mask(x, y) == other.mask(x, y) })
+            })
+    }
+}
+
+impl<T> Boundary<T> {
+    /// Map a point that may lie outside of a frame with the given dimensions
+    /// to the point inside of the frame that it refers to. Returns None if the
+    /// point is outside of the frame and the boundary is constant, or if the
+    /// frame has no squares
+    pub fn resolve(&self, x: isize, y: isize, width: usize, height: usize)
+        -> Option<(usize, usize)> {
+        if width == 0 || height == 0 {
+            return None;
+        }
+        let (w, h) = (width as isize, height as isize);
+        let (x, y) = match *self {
+            Boundary::Toroidal => (x.rem_euclid(w), y.rem_euclid(h)),
+            Boundary::Constant(_) => {
+                if x < 0 || x >= w || y < 0 || y >= h {
+                    return None;
+                }
+                (x, y)
+            },
+            Boundary::Reflecting => (reflect(x, w), reflect(y, h)),
+            Boundary::KleinBottle => {
+                let y = if x.div_euclid(w) % 2 == 0 { y } else { -1 - y };
+                (x.rem_euclid(w), y.rem_euclid(h))
+            },
+            Boundary::Helical(shift) => {
+                let y = y + x.div_euclid(w) * shift;
+                (x.rem_euclid(w), y.rem_euclid(h))
+            },
+        };
+        Some((x as usize, y as usize))
+    }
+}
+
+/// Fold x into 0..m as if there were mirrors at both ends of the range
+pub(crate) fn reflect(x: isize, m: isize) -> isize {
+    let x = x.rem_euclid(2 * m);
+    if x < m { x } else { 2 * m - 1 - x }
+}
+
+/// Marks a square that rules never overwrite
+#[derive(Clone, Debug, PartialEq)]
+pub enum Mask<T> {
+    /// The square keeps its state, and rules can tell that it is a wall with
+    /// Square::is_wall
+    Wall,
+    /// The square is empty, i.e. has the default value, and stays empty, so
+    /// anything that moves into it disappears
+    Absorbing,
+    /// The square has the given state every generation
+    Emitter(T),
+}
+
 /// Represents a single square in the frame
 #[derive(Debug, PartialEq)]
 pub struct Square<'a, T>
@@ -47,30 +240,43 @@ where T: 'a {
     point: (usize, usize),
 }
 
-/// Add x and y mod m
-fn add_modulo(x: usize, y: isize, m: usize) -> usize {
-    // y should not be greater than the modulo we are working with
-    assert!((y.abs() as usize) < m);
-    let base = (x % m) + m;
-    let delta = if y.is_negative() { y + (m as isize) } else { y } as usize;
-    (base + delta) % m
-}
-
 impl<'a, T> Square<'a, T>
 where T: 'a {
-    /// Return a point relative to the square
+    /// Return a point relative to the square, points outside of the frame are
+    /// looked up according to the frame's boundary condition
     pub fn get(&self, i: isize, j: isize) -> &T {
         let (x, y) = self.point;
-        let width = self.frame.width();
-        let height = self.frame.height();
-        let (x, y) = (add_modulo(x, i, width), add_modulo(y, j, height));
-        self.frame.get(x, y)
+        self.frame.at(x as isize + i, y as isize + j)
+    }
+
+    /// Whether the point relative to the square is a wall. Walls hold a state
+    /// like any other square, which is what get returns for them, so rules
+    /// that treat walls differently from that state check this first. Points
+    /// outside of a frame with a constant boundary are not walls
+    pub fn is_wall(&self, i: isize, j: isize) -> bool {
+        let (x, y) = self.point;
+        let point = self.frame.resolve(x as isize + i, y as isize + j);
+        matches!(point.and_then(|(x, y)| { self.frame.mask(x, y) }),
+                 Some(&Mask::Wall))
+    }
+
+    /// Whether the square is a triangle that points up on a triangular
+    /// lattice, which is when x + y is even. See Neighborhood::TriangularEdges
+    /// for the boundaries that keep this consistent across the edges
+    pub fn points_up(&self) -> bool {
+        let (x, y) = self.point;
+        (x + y) % 2 == 0
     }
 
     /// The coordinate of the square
     pub fn coordinate(&self) -> (usize, usize) {
         self.point
     }
+
+    /// The frame that the square is in
+    pub fn frame(&self) -> &'a Frame<T> {
+        self.frame
+    }
 }
 
 impl<'a, T> Square<'a, T>
@@ -78,15 +284,9 @@ where T: 'a + Clone {
     /// Return a nondeterministic Vec of &T's within a square with side length r
     /// self in an ortholinear fashion. Does not contain the current node.
     pub fn within_ortholinear(&self, r: isize) -> Vec<T> {
-        let mut nodes = vec![];
-        for i in -r..r + 1 {
-            for j in -r..r + 1 {
-                if i != 0 || j != 0 {
-                    nodes.push(self.get(i, j).clone());
-                }
-            }
-        }
-        nodes
+        self.neighbors(&Neighborhood::Moore(r.unsigned_abs()))
+            .cloned()
+            .collect()
     }
 }
 
@@ -97,23 +297,88 @@ where T: Clone {
     /// board
     pub fn next_frame<F>(&self, step: F) -> Frame<T>
     where F: Fn(Square<T>) -> T {
-        let mut data = self.data.clone();
-        for (x, y, _) in self.enumerate_squares() {
-            let square = Square {
-                frame: &self,
-                point: (x, y),
-            };
-            data[self.height * y + x] = step(square);
+        let mut next = self.clone();
+        self.step_into(&mut next, step);
+        next
+    }
+
+    /// Make out the same size as this frame so that every cell can be
+    /// overwritten, reusing its storage when it already is
+    fn prepare(&self, out: &mut Frame<T>) {
+        if out.width != self.width || out.height != self.height {
+            out.data.clone_from(&self.data);
+            out.width = self.width;
+            out.height = self.height;
         }
+        out.boundary.clone_from(&self.boundary);
+        out.masks.clone_from(&self.masks);
+    }
 
-        Frame {
-            data: data,
-            width: self.width(),
-            height: self.height(),
+    /// The state that the masked square at (x, y) has in the next generation,
+    /// or None if the square isn't masked and a rule should compute it
+    pub(crate) fn fixed(&self, x: usize, y: usize) -> Option<T> {
+        match self.mask(x, y) {
+            None => None,
+            Some(Mask::Emitter(value)) => Some(value.clone()),
+            Some(_) => Some(self.get(x, y).clone()),
+        }
+    }
+
+    /// The state that the square at index i has in the next generation
+    fn next_value<F>(&self, i: usize, step: &F) -> T
+    where F: Fn(Square<T>) -> T {
+        let (x, y) = (i % self.width, i / self.width);
+        self.fixed(x, y).unwrap_or_else(|| { step(self.square(x, y)) })
+    }
+
+    /// compute the next frame of the simulation like next_frame, but write it
+    /// into out instead of allocating a new frame. out takes on the size and
+    /// boundary condition of this frame
+    pub fn step_into<F>(&self, out: &mut Frame<T>, step: F)
+    where F: Fn(Square<T>) -> T {
+        self.prepare(out);
+        for (i, cell) in out.data.iter_mut().enumerate() {
+            *cell = self.next_value(i, &step);
         }
     }
 }
 
+impl<T> Frame<T>
+where T: Clone + Send + Sync {
+    /// return the next frame of the simulation like next_frame, but split the
+    /// frame into bands of rows that are computed on separate threads. The
+    /// result is the same as next_frame as long as the step function is
+    /// deterministic
+    pub fn next_frame_parallel<F>(&self, threads: usize, step: F) -> Frame<T>
+    where F: Fn(Square<T>) -> T + Sync {
+        let mut next = self.clone();
+        self.step_into_parallel(&mut next, threads, step);
+        next
+    }
+
+    /// compute the next frame of the simulation like next_frame_parallel, but
+    /// write it into out instead of allocating a new frame
+    pub fn step_into_parallel<F>(&self, out: &mut Frame<T>, threads: usize,
+                                 step: F)
+    where F: Fn(Square<T>) -> T + Sync {
+        self.prepare(out);
+        let band = self.height.div_ceil(threads.max(1)).max(1) * self.width;
+        if band == 0 {
+            return;
+        }
+        let step = &step;
+        thread::scope(|scope| {
+            for (n, chunk) in out.data.chunks_mut(band).enumerate() {
+                scope.spawn(move || {
+                    for (i, cell) in chunk.iter_mut().enumerate() {
+                        *cell = self.next_value(n * band + i, step);
+                    }
+                });
+            }
+        });
+    }
+}
+
 /// An iterator over a Frame
 #[derive(Debug, Clone, PartialEq)]
 pub struct FrameIterator<'a, T>
@@ -128,7 +393,7 @@ where T: 'a {
 
     fn next(&mut self) -> Option<(usize, usize, &'a T)> {
         let (x, y) = self.next_index;
-        if y < self.frame.width() {
+        if x < self.frame.width() && y < self.frame.height() {
             let val = self.frame.get(x, y);
             self.next_index =
                 if x + 1 < self.frame.width() { (x + 1, y) }
@@ -170,17 +435,40 @@ where T: 'a {
 impl<T> Frame<T> {
     /// Returns an iterator over tuples of coordinate and the element at that
     /// coordinate
-    pub fn enumerate_squares(&self) -> FrameIterator<T> {
+    pub fn enumerate_squares(&self) -> FrameIterator<'_, T> {
         FrameIterator {
-            frame: &self,
+            frame: self,
             next_index: (0, 0),
         }
     }
 }
 
 #[cfg(test)]
-mod tests {
-    use super::Frame;
+pub(crate) mod tests {
+    use super::{Boundary, Frame, Mask};
+    use super::super::game_of_life::State;
+
+    /// A frame with an irregular pattern where each square takes one of the
+    /// given states, or keeps the default value where there are too few
+    pub(crate) fn pattern<T>(w: usize, h: usize, boundary: Boundary<T>,
+                             states: &[T]) -> Frame<T>
+    where T: Default + Clone {
+        let mut frame = Frame::new(w, h).with_boundary(boundary);
+        for y in 0..h {
+            for x in 0..w {
+                if let Some(state) = states.get((x * 7 + y * 13 + x * y) % 5) {
+                    *frame.get_mut(x, y) = state.clone();
+                }
+            }
+        }
+        frame
+    }
+
+    /// A frame with an irregular pattern of alive squares
+    pub(crate) fn scattered(w: usize, h: usize, boundary: Boundary<State>)
+        -> Frame<State> {
+        pattern(w, h, boundary, &[State::Alive, State::Alive])
+    }
 
     #[test]
     fn frame_init() {
@@ -215,4 +503,178 @@ mod tests {
 
         assert_eq!(frame1, frame2);
     }
+
+    #[test]
+    fn frame_next_parallel() {
+        let mut frame = Frame::<i32>::new(7, 5);
+        for (i, (x, y)) in [(0, 0), (3, 1), (6, 4), (2, 2)].iter().enumerate() {
+            *frame.get_mut(*x, *y) = i as i32 + 1;
+        }
+        let step = |sq: super::Square<i32>| {
+            sq.get(-1, 0) * 3 + sq.get(1, 1) - sq.get(0, -1)
+        };
+
+        let serial = frame.next_frame(step);
+        for threads in 0..8 {
+            assert_eq!(frame.next_frame_parallel(threads, step), serial);
+        }
+        let empty = Frame::<i32>::new(0, 3);
+        assert_eq!(empty.next_frame_parallel(4, step), empty);
+    }
+
+    #[test]
+    fn frame_step_into() {
+        let mut frame = Frame::<i32>::new(3, 2);
+        *frame.get_mut(1, 1) = 4;
+        let step = |sq: super::Square<i32>| { sq.get(0, 1) + 1 };
+
+        // a frame of the same size keeps its storage
+        let mut out = Frame::<i32>::new(3, 2);
+        let storage = out.data.as_ptr();
+        frame.step_into(&mut out, step);
+        assert_eq!(out, frame.next_frame(step));
+        assert_eq!(out.data.as_ptr(), storage);
+
+        // other frames are resized to fit
+        let mut out = Frame::<i32>::new_finite(1, 1);
+        frame.step_into_parallel(&mut out, 2, step);
+        assert_eq!(out, frame.next_frame(step));
+    }
+
+    #[test]
+    fn frame_rectangular() {
+        let mut frame = Frame::<i32>::new(3, 2);
+        *frame.get_mut(2, 0) = 1;
+        *frame.get_mut(0, 1) = 2;
+        assert_eq!(*frame.get(2, 0), 1);
+        assert_eq!(*frame.get(0, 1), 2);
+        assert_eq!(frame.enumerate_squares().count(), 6);
+    }
+
+    #[test]
+    fn empty_frames() {
+        let boundaries = [Boundary::Toroidal, Boundary::Constant(7),
+                          Boundary::Reflecting, Boundary::KleinBottle,
+                          Boundary::Helical(1)];
+        for &(w, h) in [(0, 0), (0, 5), (5, 0)].iter() {
+            for boundary in boundaries.iter() {
+                let frame = Frame::<i32>::new(w, h)
+                    .with_boundary(boundary.clone());
+                assert_eq!(frame.enumerate_squares().count(), 0);
+                assert_eq!(frame.resolve(-1, 2), None);
+                let step = |sq: super::Square<i32>| { sq.get(-1, 1) + 1 };
+                assert_eq!(frame.next_frame(step), frame);
+                assert_eq!(frame.next_frame_parallel(3, step), frame);
+            }
+            let finite = Frame::<i32>::new(w, h)
+                .with_boundary(Boundary::Constant(7));
+            assert_eq!(*finite.at(-1, 2), 7);
+        }
+    }
+
+    /// A 3 by 2 frame with every cell holding a unique value
+    fn numbered(boundary: Boundary<i32>) -> Frame<i32> {
+        let mut frame = Frame::<i32>::new(3, 2).with_boundary(boundary);
+        for y in 0..2 {
+            for x in 0..3 {
+                *frame.get_mut(x, y) = (y * 3 + x) as i32;
+            }
+        }
+        frame
+    }
+
+    #[test]
+    fn boundary_toroidal() {
+        let frame = numbered(Boundary::Toroidal);
+        assert_eq!(*frame.at(-1, 0), 2);
+        assert_eq!(*frame.at(3, 1), 3);
+        assert_eq!(*frame.at(0, -1), 3);
+        assert_eq!(*frame.at(7, 5), 4);
+    }
+
+    #[test]
+    fn boundary_constant() {
+        let frame = numbered(Boundary::Constant(-1));
+        assert_eq!(*frame.at(-1, 0), -1);
+        assert_eq!(*frame.at(0, 2), -1);
+        assert_eq!(*frame.at(2, 1), 5);
+        assert_eq!(frame.resolve(3, 0), None);
+    }
+
+    #[test]
+    fn boundary_reflecting() {
+        let frame = numbered(Boundary::Reflecting);
+        assert_eq!(*frame.at(-1, 0), 0);
+        assert_eq!(*frame.at(-2, 0), 1);
+        assert_eq!(*frame.at(3, 1), 5);
+        assert_eq!(*frame.at(1, -1), 1);
+        assert_eq!(*frame.at(1, 2), 4);
+    }
+
+    #[test]
+    fn boundary_klein_bottle() {
+        let frame = numbered(Boundary::KleinBottle);
+        assert_eq!(*frame.at(-1, 0), 5);
+        assert_eq!(*frame.at(3, 1), 0);
+        assert_eq!(*frame.at(6, 1), 3);
+        assert_eq!(*frame.at(1, -1), 4);
+    }
+
+    #[test]
+    fn boundary_helical() {
+        let frame = numbered(Boundary::Helical(1));
+        assert_eq!(*frame.at(3, 0), 3);
+        assert_eq!(*frame.at(-1, 1), 2);
+        assert_eq!(*frame.at(-1, 0), 5);
+    }
+
+    #[test]
+    fn masks() {
+        let mut frame = numbered(Boundary::Toroidal);
+        assert!(!frame.is_masked());
+        frame.set_mask(1, 0, Mask::Wall);
+        frame.set_mask(2, 0, Mask::Absorbing);
+        frame.mask_rect(0, 1, 2, 1, Mask::Emitter(9));
+        assert_eq!(*frame.get(1, 0), 1);
+        assert_eq!(*frame.get(2, 0), 0);
+        assert_eq!(*frame.get(0, 1), 9);
+        assert_eq!(frame.mask(1, 1), Some(&Mask::Emitter(9)));
+        assert_eq!(frame.mask(2, 1), None);
+        assert!(frame.is_masked());
+
+        // walls are told apart, also across the boundary
+        let sq = frame.square(0, 0);
+        assert!(sq.is_wall(1, 0) && sq.is_wall(-2, 0));
+        assert!(!sq.is_wall(0, 0) && !sq.is_wall(2, 0));
+        let finite = frame.clone().with_boundary(Boundary::Constant(0));
+        assert!(!finite.square(0, 0).is_wall(-2, 0));
+
+        // rules never overwrite masked squares
+        let step = |sq: super::Square<i32>| { sq.get(-1, 0) + 10 };
+        let next = frame.next_frame(step);
+        assert_eq!(next.enumerate_squares().map(|(_, _, &v)| { v })
+                       .collect::<Vec<_>>(),
+                   vec![10, 1, 0, 9, 9, 19]);
+        assert_eq!(frame.next_frame_parallel(2, step), next);
+        assert_eq!(next.mask(1, 0), Some(&Mask::Wall));
+
+        frame.clear_mask(1, 0);
+        assert_eq!(*frame.next_frame(step).get(1, 0), 10);
+
+        // a frame whose masks were all cleared is like one that never had any
+        let mut cleared = numbered(Boundary::Toroidal);
+        cleared.set_mask(1, 0, Mask::Wall);
+        assert!(cleared != numbered(Boundary::Toroidal));
+        cleared.clear_mask(1, 0);
+        assert_eq!(cleared, numbered(Boundary::Toroidal));
+    }
+
+    #[test]
+    fn boundary_kept_by_next_frame() {
+        let frame = Frame::<i32>::new_finite(2, 2);
+        let next = frame.next_frame(|sq| *sq.get(-1, 0) + 1);
+        assert_eq!(*next.boundary(), Boundary::Constant(0));
+        assert_eq!(*next.get(0, 0), 1);
+        assert_eq!(*next.get(1, 0), 1);
+    }
 }
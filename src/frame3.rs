@@ -0,0 +1,492 @@
+use super::{Boundary, FiniteState};
+use super::frame::reflect;
+
+use std::io::{self, Write};
+
+/// A frame of a simulation in three dimensions, a box of cubes that are
+/// advanced like the squares of a Frame
+#[derive(Clone, Debug, PartialEq)]
+pub struct Frame3<T> {
+    data: Vec<T>,
+    width: usize,
+    height: usize,
+    depth: usize,
+    boundary: Boundary<T>,
+}
+
+impl<T> Frame3<T>
+where T: Default + Clone {
+    /// Creates an empty frame
+    pub fn new(x: usize, y: usize, z: usize) -> Frame3<T> {
+        Frame3 {
+            data: vec![T::default(); x * y * z],
+            width: x,
+            height: y,
+            depth: z,
+            boundary: Boundary::default(),
+        }
+    }
+
+    /// Creates an empty frame whose cubes outside of the frame are dead, i.e.
+    /// have the default value
+    pub fn new_finite(x: usize, y: usize, z: usize) -> Frame3<T> {
+        Frame3::new(x, y, z).with_boundary(Boundary::Constant(T::default()))
+    }
+}
+
+/// Getters / setters for the data in the frame
+impl<T> Frame3<T> {
+    /// the width of the frame
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    /// the height of the frame
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    /// the depth of the frame
+    pub fn depth(&self) -> usize {
+        self.depth
+    }
+
+    /// the index of (x, y, z) in the data
+    fn index(&self, x: usize, y: usize, z: usize) -> usize {
+        (z * self.height + y) * self.width + x
+    }
+
+    /// the data at (x, y, z)
+    pub fn get(&self, x: usize, y: usize, z: usize) -> &T {
+        &self.data[self.index(x, y, z)]
+    }
+
+    /// get a mutable reference to the data at (x, y, z)
+    pub fn get_mut(&mut self, x: usize, y: usize, z: usize) -> &mut T {
+        let i = self.index(x, y, z);
+        &mut self.data[i]
+    }
+
+    /// the cube at (x, y, z), as seen by a step function
+    pub fn square(&self, x: usize, y: usize, z: usize) -> Square3<'_, T> {
+        Square3 {
+            frame: self,
+            point: (x, y, z),
+        }
+    }
+
+    /// the boundary condition of the frame
+    pub fn boundary(&self) -> &Boundary<T> {
+        &self.boundary
+    }
+
+    /// change the boundary condition of the frame
+    pub fn set_boundary(&mut self, boundary: Boundary<T>) {
+        self.boundary = boundary;
+    }
+
+    /// the frame with its boundary condition replaced
+    pub fn with_boundary(mut self, boundary: Boundary<T>) -> Frame3<T> {
+        self.boundary = boundary;
+        self
+    }
+
+    /// Map a point that may lie outside of the frame to the point inside of the
+    /// frame that it refers to. Returns None if the point is outside of the
+    /// frame and the boundary is constant, or if the frame has no cubes.
+    /// Boundaries that glue the edges of a 2D frame together with a twist or a
+    /// shift do so to the x and y axes of every layer, and the layers at the
+    /// front and back are glued together
+    pub fn resolve(&self, x: isize, y: isize, z: isize)
+        -> Option<(usize, usize, usize)> {
+        if self.depth == 0 {
+            return None;
+        }
+        let d = self.depth as isize;
+        let z = match self.boundary {
+            Boundary::Constant(_) if z < 0 || z >= d => return None,
+            Boundary::Constant(_) => z,
+            Boundary::Reflecting => reflect(z, d),
+            _ => z.rem_euclid(d),
+        };
+        self.boundary.resolve(x, y, self.width, self.height)
+            .map(|(x, y)| { (x, y, z as usize) })
+    }
+
+    /// the data at (x, y, z) where (x, y, z) may lie outside of the frame, in
+    /// which case the boundary condition decides what is there. Panics if the
+    /// frame has no cubes and the boundary isn't constant
+    pub fn at(&self, x: isize, y: isize, z: isize) -> &T {
+        match self.resolve(x, y, z) {
+            Some((x, y, z)) => self.get(x, y, z),
+            None => match self.boundary {
+                Boundary::Constant(ref value) => value,
+                _ => panic!("a {}x{}x{} frame has no cubes to wrap around to",
+                            self.width, self.height, self.depth),
+            },
+        }
+    }
+
+    /// Returns an iterator over tuples of coordinate and the element at that
+    /// coordinate, with x changing fastest and z slowest
+    pub fn enumerate_cubes(&self) -> Frame3Iterator<'_, T> {
+        Frame3Iterator {
+            frame: self,
+            next_index: 0,
+        }
+    }
+}
+
+impl<T> Frame3<T>
+where T: Clone {
+    /// return the next frame of the simulation advancing the simulation using
+    /// a step function that computes the value for any cube given a certain
+    /// board
+    pub fn next_frame<F>(&self, step: F) -> Frame3<T>
+    where F: Fn(Square3<T>) -> T {
+        let mut next = self.clone();
+        self.step_into(&mut next, step);
+        next
+    }
+
+    /// compute the next frame of the simulation like next_frame, but write it
+    /// into out instead of allocating a new frame. out takes on the size and
+    /// boundary condition of this frame
+    pub fn step_into<F>(&self, out: &mut Frame3<T>, step: F)
+    where F: Fn(Square3<T>) -> T {
+        if (out.width, out.height, out.depth)
+            != (self.width, self.height, self.depth) {
+            out.data.clone_from(&self.data);
+            out.width = self.width;
+            out.height = self.height;
+            out.depth = self.depth;
+        }
+        out.boundary.clone_from(&self.boundary);
+        for (i, cell) in out.data.iter_mut().enumerate() {
+            let (x, yz) = (i % self.width, i / self.width);
+            *cell = step(self.square(x, yz % self.height, yz / self.height));
+        }
+    }
+
+    /// return the next frame of the simulation advancing each cube with a
+    /// rule
+    pub fn advance<R>(&self, rule: &R) -> Frame3<T>
+    where R: Rule3<State = T> + ?Sized {
+        self.next_frame(|sq| { rule.step(sq) })
+    }
+
+    /// the layer of the frame at depth z as a 2D frame
+    pub fn layer(&self, z: usize) -> super::Frame<T>
+    where T: Default {
+        let mut layer = super::Frame::new(self.width, self.height)
+            .with_boundary(self.boundary.clone());
+        for y in 0..self.height {
+            for x in 0..self.width {
+                *layer.get_mut(x, y) = self.get(x, y, z).clone();
+            }
+        }
+        layer
+    }
+}
+
+impl<T> Frame3<T>
+where T: FiniteState {
+    /// Write the frame in the legacy VTK format as structured points with the
+    /// index of the state of every cube as cell data, which viewers like
+    /// ParaView can open. The title must fit on a single line
+    pub fn write_vtk<W>(&self, mut out: W, title: &str) -> io::Result<()>
+    where W: Write {
+        writeln!(out, "# vtk DataFile Version 3.0")?;
+        writeln!(out, "{}", title)?;
+        writeln!(out, "ASCII")?;
+        writeln!(out, "DATASET STRUCTURED_POINTS")?;
+        writeln!(out, "DIMENSIONS {} {} {}",
+                 self.width + 1, self.height + 1, self.depth + 1)?;
+        writeln!(out, "ORIGIN 0 0 0")?;
+        writeln!(out, "SPACING 1 1 1")?;
+        writeln!(out, "CELL_DATA {}", self.data.len())?;
+        writeln!(out, "SCALARS state int 1")?;
+        writeln!(out, "LOOKUP_TABLE default")?;
+        for row in self.data.chunks(self.width.max(1)) {
+            let row = row.iter().map(|e| { e.index().to_string() })
+                .collect::<Vec<_>>();
+            writeln!(out, "{}", row.join(" "))?;
+        }
+        Ok(())
+    }
+}
+
+/// An iterator over a Frame3
+#[derive(Debug, Clone, PartialEq)]
+pub struct Frame3Iterator<'a, T>
+where T: 'a {
+    frame: &'a Frame3<T>,
+    next_index: usize,
+}
+
+impl<'a, T> Iterator for Frame3Iterator<'a, T>
+where T: 'a {
+    type Item = (usize, usize, usize, &'a T);
+
+    fn next(&mut self) -> Option<(usize, usize, usize, &'a T)> {
+        let i = self.next_index;
+        let frame = self.frame;
+        frame.data.get(i).map(|val| {
+            self.next_index += 1;
+            let (x, yz) = (i % frame.width, i / frame.width);
+            (x, yz % frame.height, yz / frame.height, val)
+        })
+    }
+}
+
+/// The set of points, relative to a cube, that make up its neighbors
+#[derive(Clone, Debug, PartialEq)]
+pub enum Neighborhood3 {
+    /// Every point within a cube of the given radius, the 26 cubes that share
+    /// a face, an edge or a corner for a radius of 1
+    Moore(usize),
+    /// Every point within the given Manhattan distance, the 6 cubes that share
+    /// a face for a radius of 1
+    VonNeumann(usize),
+}
+
+/// The 26 neighbors of a cube
+pub const MOORE3: Neighborhood3 = Neighborhood3::Moore(1);
+
+/// The 6 neighbors of a cube that share one of its faces
+pub const VON_NEUMANN3: Neighborhood3 = Neighborhood3::VonNeumann(1);
+
+impl Neighborhood3 {
+    /// The largest distance along any axis of any neighbor
+    pub fn radius(&self) -> usize {
+        match *self {
+            Neighborhood3::Moore(r) | Neighborhood3::VonNeumann(r) => r,
+        }
+    }
+
+    /// Whether the offset (i, j, k) is a neighbor. The origin never is
+    pub fn contains(&self, i: isize, j: isize, k: isize) -> bool {
+        let (a, b, c) = (i.unsigned_abs(), j.unsigned_abs(), k.unsigned_abs());
+        match *self {
+            _ if a == 0 && b == 0 && c == 0 => false,
+            Neighborhood3::Moore(r) => a <= r && b <= r && c <= r,
+            Neighborhood3::VonNeumann(r) => a + b + c <= r,
+        }
+    }
+
+    /// The offsets of every neighbor
+    pub fn offsets(&self) -> Vec<(isize, isize, isize)> {
+        let r = self.radius() as isize;
+        let mut offsets = Vec::new();
+        for k in -r..r + 1 {
+            for j in -r..r + 1 {
+                for i in -r..r + 1 {
+                    if self.contains(i, j, k) {
+                        offsets.push((i, j, k));
+                    }
+                }
+            }
+        }
+        offsets
+    }
+
+    /// The number of neighbors in the neighborhood
+    pub fn len(&self) -> usize {
+        self.offsets().len()
+    }
+
+    /// Whether there are no neighbors in the neighborhood
+    pub fn is_empty(&self) -> bool {
+        self.radius() == 0
+    }
+}
+
+/// Represents a single cube in the frame
+#[derive(Debug, PartialEq)]
+pub struct Square3<'a, T>
+where T: 'a {
+    frame: &'a Frame3<T>,
+    point: (usize, usize, usize),
+}
+
+impl<'a, T> Square3<'a, T>
+where T: 'a {
+    /// Return a point relative to the cube, points outside of the frame are
+    /// looked up according to the frame's boundary condition
+    pub fn get(&self, i: isize, j: isize, k: isize) -> &T {
+        let (x, y, z) = self.point;
+        self.frame.at(x as isize + i, y as isize + j, z as isize + k)
+    }
+
+    /// The coordinate of the cube
+    pub fn coordinate(&self) -> (usize, usize, usize) {
+        self.point
+    }
+
+    /// The frame that the cube is in
+    pub fn frame(&self) -> &'a Frame3<T> {
+        self.frame
+    }
+
+    /// The number of neighbors that satisfy a predicate
+    pub fn count_neighbors<P>(&self, neighborhood: &Neighborhood3, pred: P)
+        -> usize
+    where P: Fn(&T) -> bool {
+        let r = neighborhood.radius() as isize;
+        let mut n = 0;
+        for k in -r..r + 1 {
+            for j in -r..r + 1 {
+                for i in -r..r + 1 {
+                    if neighborhood.contains(i, j, k) && pred(self.get(i, j, k))
+                    {
+                        n += 1;
+                    }
+                }
+            }
+        }
+        n
+    }
+}
+
+/// A rule that computes the next state of a cube from its surroundings
+pub trait Rule3 {
+    /// The state of a single cube
+    type State;
+
+    /// A human readable name for the rule
+    fn name(&self) -> &str;
+
+    /// The neighborhood that the rule looks at to compute the next state
+    fn neighborhood(&self) -> Neighborhood3 {
+        MOORE3
+    }
+
+    /// Compute the next state of a cube
+    fn step(&self, curr: Square3<Self::State>) -> Self::State;
+}
+
+impl<R> Rule3 for &R
+where R: Rule3 + ?Sized {
+    type State = R::State;
+
+    fn name(&self) -> &str { (**self).name() }
+    fn neighborhood(&self) -> Neighborhood3 { (**self).neighborhood() }
+
+    fn step(&self, curr: Square3<Self::State>) -> Self::State {
+        (**self).step(curr)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::{Boundary, FiniteState};
+    use super::{Frame3, Neighborhood3, MOORE3, VON_NEUMANN3};
+
+    /// A 3 by 2 by 2 frame with every cube holding a unique value
+    fn numbered(boundary: Boundary<i32>) -> Frame3<i32> {
+        let mut frame = Frame3::<i32>::new(3, 2, 2).with_boundary(boundary);
+        for (i, cube) in frame.data.iter_mut().enumerate() {
+            *cube = i as i32;
+        }
+        frame
+    }
+
+    #[test]
+    fn frame_init() {
+        let frame = numbered(Boundary::Toroidal);
+        assert_eq!((frame.width(), frame.height(), frame.depth()), (3, 2, 2));
+        assert_eq!(*frame.get(2, 1, 0), 5);
+        assert_eq!(*frame.get(1, 0, 1), 7);
+        let cubes = frame.enumerate_cubes()
+            .map(|(x, y, z, &v)| { (x, y, z, v) })
+            .collect::<Vec<_>>();
+        assert_eq!(cubes.len(), 12);
+        assert_eq!(cubes[10], (1, 1, 1, 10));
+        assert_eq!(*frame.layer(1).get(2, 0), 8);
+    }
+
+    #[test]
+    fn boundaries() {
+        let frame = numbered(Boundary::Toroidal);
+        assert_eq!(*frame.at(-1, 0, 0), 2);
+        assert_eq!(*frame.at(0, 0, -1), 6);
+        assert_eq!(*frame.at(4, 3, 2), 4);
+
+        let frame = numbered(Boundary::Constant(-1));
+        assert_eq!(*frame.at(0, 0, 2), -1);
+        assert_eq!(*frame.at(2, 1, 1), 11);
+        assert_eq!(frame.resolve(0, -1, 0), None);
+
+        let frame = numbered(Boundary::Reflecting);
+        assert_eq!(*frame.at(0, 0, -1), 0);
+        assert_eq!(*frame.at(1, 0, 2), 7);
+    }
+
+    #[test]
+    fn empty_frames() {
+        for &(w, h, d) in [(0, 0, 0), (3, 2, 0), (0, 2, 2)].iter() {
+            let frame = Frame3::<i32>::new(w, h, d);
+            assert_eq!(frame.enumerate_cubes().count(), 0);
+            assert_eq!(frame.resolve(-1, 0, 1), None);
+            assert_eq!(frame.next_frame(|sq| { *sq.get(0, 0, 1) }), frame);
+            let finite = Frame3::<i32>::new_finite(w, h, d);
+            assert_eq!(*finite.at(-1, 0, 1), 0);
+        }
+    }
+
+    #[test]
+    fn neighborhoods() {
+        assert_eq!(MOORE3.len(), 26);
+        assert_eq!(VON_NEUMANN3.len(), 6);
+        assert_eq!(Neighborhood3::Moore(2).len(), 124);
+        assert!(VON_NEUMANN3.contains(0, 0, -1));
+        assert!(!VON_NEUMANN3.contains(1, 0, -1));
+        assert!(MOORE3.contains(1, -1, 1) && !MOORE3.contains(0, 0, 0));
+
+        let mut frame = Frame3::<i32>::new_finite(3, 3, 3);
+        *frame.get_mut(1, 1, 0) = 1;
+        *frame.get_mut(0, 0, 0) = 1;
+        *frame.get_mut(2, 2, 2) = 1;
+        let sq = frame.square(1, 1, 1);
+        assert_eq!(sq.count_neighbors(&MOORE3, |&e| { e == 1 }), 3);
+        assert_eq!(sq.count_neighbors(&VON_NEUMANN3, |&e| { e == 1 }), 1);
+    }
+
+    #[test]
+    fn frame_next() {
+        let frame = numbered(Boundary::Constant(100));
+        let next = frame.next_frame(|sq| { *sq.get(0, 0, 1) });
+        assert_eq!(*next.boundary(), Boundary::Constant(100));
+        assert_eq!(*next.get(1, 1, 0), 10);
+        assert_eq!(*next.get(1, 1, 1), 100);
+
+        let mut out = Frame3::<i32>::new(1, 1, 1);
+        frame.step_into(&mut out, |sq| { *sq.get(0, 0, 1) });
+        assert_eq!(out, next);
+    }
+
+    /// Two states that are told apart by their index
+    #[derive(Clone, Copy, Debug, PartialEq, Default)]
+    struct Bit(bool);
+
+    impl FiniteState for Bit {
+        fn count() -> usize { 2 }
+        fn index(&self) -> usize { self.0 as usize }
+        fn from_index(index: usize) -> Bit { Bit(index == 1) }
+    }
+
+    #[test]
+    fn vtk() {
+        let mut frame = Frame3::<Bit>::new(2, 1, 2);
+        *frame.get_mut(1, 0, 1) = Bit(true);
+        let mut out = Vec::new();
+        frame.write_vtk(&mut out, "test").unwrap();
+        let text = String::from_utf8(out).unwrap();
+        let lines = text.lines().collect::<Vec<_>>();
+        assert_eq!(lines[0], "# vtk DataFile Version 3.0");
+        assert_eq!(lines[1], "test");
+        assert_eq!(lines[4], "DIMENSIONS 3 2 3");
+        assert_eq!(lines[7], "CELL_DATA 4");
+        assert_eq!(&lines[10..], &["0 0", "0 1"]);
+    }
+}
@@ -1,24 +1,41 @@
-use super::Square;
+use super::{FiniteState, Frame, Neighborhood, Rule, Square, MOORE, HEXAGONAL,
+            ParseRuleError};
+use super::bit_grid::BitGrid;
+use super::rule::{split_birth_survival, parse_counts, format_counts,
+                  count_bits, find_named};
+use super::graph::{GraphRule, Node};
+
+use std::fmt;
+use std::str::FromStr;
 
 /// The state of a node in a GOL
-#[derive(Clone, Copy, Debug, PartialEq)]
+#[derive(Clone, Copy, Debug, PartialEq, Default)]
 pub enum State {
     Alive,
+    #[default]
     Dead,
 }
 
-impl Default for State {
-    fn default() -> State { State::Dead }
+impl FiniteState for State {
+    fn count() -> usize { 2 }
+
+    fn index(&self) -> usize {
+        match *self {
+            State::Dead => 0,
+            State::Alive => 1,
+        }
+    }
+
+    fn from_index(index: usize) -> State {
+        if index == 0 { State::Dead } else { State::Alive }
+    }
 }
 
 impl<'a> Square<'a, State>
 where State: 'a {
     /// Return the number of nodes alive surrounding this number
     fn alive_count(&self) -> usize {
-        self.within_ortholinear(1)
-            .iter()
-            .filter(|e| { **e == State::Alive })
-            .count()
+        self.count_neighbors(&MOORE, |e| { *e == State::Alive })
     }
 }
 
@@ -40,10 +57,492 @@ pub fn rule(curr: Square<State>) -> State {
     }
 }
 
+/// Conway's Game of Life as a Rule
+#[derive(Clone, Copy, Debug, PartialEq, Default)]
+pub struct GameOfLife;
+
+impl Rule for GameOfLife {
+    type State = State;
+
+    fn name(&self) -> &str { "Conway's Game of Life" }
+    fn state_count(&self) -> Option<usize> { Some(2) }
+
+    fn step(&self, curr: Square<State>) -> State { rule(curr) }
+
+    fn advance_frame(&self, frame: &Frame<State>) -> Option<Frame<State>> {
+        LifeLike::conway().advance_frame(frame)
+    }
+}
+
+/// Conway's Game of Life on a graph, where the neighbors of a node are the
+/// nodes it is connected to
+impl GraphRule for GameOfLife {
+    type State = State;
+
+    fn name(&self) -> &str { "Conway's Game of Life" }
+
+    fn step(&self, node: Node<State>) -> State {
+        let n = node.count_neighbors(|e| { *e == State::Alive });
+        match (*node.get(), n) {
+            (State::Alive, 2) | (_, 3) => State::Alive,
+            _ => State::Dead,
+        }
+    }
+}
+
+/// Well known Life-like rules and their rulestrings
+pub const CATALOG: &[(&str, &str)] = &[
+    ("Life", "B3/S23"),
+    ("HighLife", "B36/S23"),
+    ("Day & Night", "B3678/S34678"),
+    ("Seeds", "B2/S"),
+    ("Life without Death", "B3/S012345678"),
+    ("Maze", "B3/S12345"),
+    ("Mazectric", "B3/S1234"),
+    ("2x2", "B36/S125"),
+    ("34 Life", "B34/S34"),
+    ("Replicator", "B1357/S1357"),
+    ("Diamoeba", "B35678/S5678"),
+    ("Morley", "B368/S245"),
+    ("Anneal", "B4678/S35678"),
+    ("Coral", "B3/S45678"),
+    ("Gnarl", "B1/S1"),
+    ("Long Life", "B345/S5"),
+];
+
+/// The suffixes of rulestrings for lattices other than the square one, and the
+/// neighborhoods that they count
+const LATTICES: [(&str, Neighborhood); 4] = [
+    ("H", HEXAGONAL),
+    ("TE", Neighborhood::TriangularEdges),
+    ("TV", Neighborhood::TriangularVertices),
+    ("T", Neighborhood::Triangular),
+];
+
+/// An outer totalistic rule over the states of Conway's Game of Life, where
+/// whether a square is alive next depends only on whether it is alive now and
+/// how many of its neighbors are alive. The neighbors are either the Moore
+/// neighborhood, the six neighbors on a hexagonal lattice or one of the
+/// neighborhoods on a triangular lattice
+#[derive(Clone, Debug, PartialEq)]
+pub struct LifeLike {
+    name: String,
+    neighborhood: Neighborhood,
+    /// bit n is set if a dead square with n alive neighbors becomes alive
+    birth: u32,
+    /// bit n is set if an alive square with n alive neighbors stays alive
+    survival: u32,
+}
+
+impl LifeLike {
+    /// Create a rule from the neighbor counts that cause birth and survival.
+    /// Panics if a count is larger than the number of neighbors
+    pub fn new(birth: &[usize], survival: &[usize]) -> LifeLike {
+        LifeLike::with_neighborhood(MOORE, birth, survival)
+    }
+
+    /// Create a rule for a hexagonal lattice from the neighbor counts that
+    /// cause birth and survival, which go up to 6
+    pub fn hexagonal(birth: &[usize], survival: &[usize]) -> LifeLike {
+        LifeLike::with_neighborhood(HEXAGONAL, birth, survival)
+    }
+
+    /// Create a rule for a triangular lattice from the neighborhood, which must
+    /// be one of the triangular ones, and the neighbor counts that cause birth
+    /// and survival. Frames it advances need a constant boundary, or a
+    /// toroidal one with an even width and height
+    pub fn triangular(neighborhood: Neighborhood, birth: &[usize],
+                      survival: &[usize]) -> LifeLike {
+        assert!(neighborhood.is_triangular(),
+                "expected a triangular neighborhood");
+        LifeLike::with_neighborhood(neighborhood, birth, survival)
+    }
+
+    /// Create a rule that counts the neighbors in a neighborhood
+    fn with_neighborhood(neighborhood: Neighborhood, birth: &[usize],
+                         survival: &[usize]) -> LifeLike {
+        let max = neighborhood.len();
+        assert!(birth.iter().chain(survival).all(|&n| { n <= max }),
+                "neighbor counts must be between 0 and {}", max);
+        let mut rule = LifeLike {
+            name: String::new(),
+            neighborhood,
+            birth: count_bits(birth),
+            survival: count_bits(survival),
+        };
+        rule.name = rule.rulestring();
+        rule
+    }
+
+    /// Conway's Game of Life, B3/S23
+    pub fn conway() -> LifeLike {
+        LifeLike::new(&[3], &[2, 3])
+    }
+
+    /// The rule in CATALOG with a name like "HighLife"
+    pub fn named(name: &str) -> Option<LifeLike> {
+        find_named(CATALOG, name).map(|(n, rule)| {
+            LifeLike { name: n.to_string(), ..rule }
+        })
+    }
+
+    /// The rule in B/S notation, e.g. "B3/S23", followed by a suffix for the
+    /// lattice if it isn't the square one: H for hexagonal, T for triangular
+    /// and TE or TV for the edges or vertices of a triangle
+    pub fn rulestring(&self) -> String {
+        let suffix = LATTICES.iter()
+            .find(|&(_, hood)| { *hood == self.neighborhood })
+            .map_or("", |&(suffix, _)| { suffix });
+        format!("B{}/S{}{}", format_counts(self.birth),
+                format_counts(self.survival), suffix)
+    }
+
+    /// Whether a dead square with n alive neighbors becomes alive
+    pub fn is_birth(&self, n: usize) -> bool {
+        self.birth.checked_shr(n as u32).unwrap_or(0) & 1 != 0
+    }
+
+    /// Whether an alive square with n alive neighbors stays alive
+    pub fn is_survival(&self, n: usize) -> bool {
+        self.survival.checked_shr(n as u32).unwrap_or(0) & 1 != 0
+    }
+
+    /// The next state of a square that is currently in state curr and has n
+    /// alive neighbors
+    pub fn next_state(&self, curr: State, n: usize) -> State {
+        let alive = match curr {
+            State::Alive => self.is_survival(n),
+            State::Dead => self.is_birth(n),
+        };
+        if alive { State::Alive } else { State::Dead }
+    }
+}
+
+impl FromStr for LifeLike {
+    type Err = ParseRuleError;
+
+    /// Parse a rulestring in B/S notation ("B36/S23"), S/B notation ("23/36")
+    /// or the name of a rule in the catalog. Rulestrings can end with the
+    /// suffix of another lattice, like "B2/S34H" for a hexagonal lattice, and
+    /// counts from 10 to 12 on a triangular lattice are written as a to c.
+    /// Triangular rules, with the suffixes T, TE and TV, need frames with a
+    /// constant boundary or a toroidal one with an even width and height
+    fn from_str(rulestring: &str) -> Result<LifeLike, ParseRuleError> {
+        if let Some(rule) = LifeLike::named(rulestring) {
+            return Ok(rule);
+        }
+
+        let trimmed = rulestring.trim();
+        let upper = trimmed.to_ascii_uppercase();
+        let (neighborhood, counts) = LATTICES.iter()
+            .find(|&&(suffix, _)| { upper.ends_with(suffix) })
+            .map_or((MOORE, trimmed), |&(suffix, ref hood)| {
+                (hood.clone(), &trimmed[..trimmed.len() - suffix.len()])
+            });
+        let max = neighborhood.len();
+        let (birth, survival) = split_birth_survival(counts)?;
+        let mut rule = LifeLike {
+            name: String::new(),
+            neighborhood,
+            birth: parse_counts(rulestring, birth, max)?,
+            survival: parse_counts(rulestring, survival, max)?,
+        };
+        rule.name = rule.rulestring();
+        Ok(rule)
+    }
+}
+
+impl fmt::Display for LifeLike {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.rulestring())
+    }
+}
+
+impl Rule for LifeLike {
+    type State = State;
+
+    fn name(&self) -> &str { &self.name }
+    fn neighborhood(&self) -> Neighborhood { self.neighborhood.clone() }
+    fn state_count(&self) -> Option<usize> { Some(2) }
+
+    fn step(&self, curr: Square<State>) -> State {
+        let n = curr.count_neighbors(&self.neighborhood,
+                                     |e| { *e == State::Alive });
+        self.next_state(*curr.get(0, 0), n)
+    }
+
+    /// Frames without masks on a square or hexagonal lattice are advanced 64
+    /// squares at a time on a bit grid
+    fn advance_frame(&self, frame: &Frame<State>) -> Option<Frame<State>> {
+        if frame.is_masked() || self.neighborhood.is_triangular() {
+            return None;
+        }
+        Some(Frame::from(&BitGrid::from(frame).next_frame(self)))
+    }
+}
+
+/// The rule on a graph, where the neighbors of a node are the nodes it is
+/// connected to rather than the rule's neighborhood, so nodes can have more
+/// neighbors than any count in the rule
+impl GraphRule for LifeLike {
+    type State = State;
+
+    fn name(&self) -> &str { &self.name }
+
+    fn step(&self, node: Node<State>) -> State {
+        let n = node.count_neighbors(|e| { *e == State::Alive });
+        self.next_state(*node.get(), n)
+    }
+}
+
+/// Well known isotropic non-totalistic rules and their rulestrings
+pub const ISOTROPIC_CATALOG: &[(&str, &str)] = &[
+    ("tlife", "B3/S2-i34q"),
+    ("Just Friends", "B2-a/S12"),
+];
+
+/// The letters of Hensel notation in the order that Golly lists them
+const HENSEL_LETTERS: &str = "ceaiknjqrytwz";
+
+/// A configuration of alive neighbors for each letter of Hensel notation and
+/// each count from 0 to 4. Bits are NW N NE W (middle) E SW S SE from lowest to
+/// highest, which is the layout Golly uses. The configurations for 5 to 8 alive
+/// neighbors are the complements of the ones for 3 to 0
+const HENSEL_CONFIGURATIONS: [&[u16]; 5] = [
+    &[],
+    &[1, 2],
+    &[5, 10, 3, 40, 33, 68],
+    &[69, 42, 11, 7, 98, 13, 14, 70, 41, 97],
+    &[325, 170, 15, 45, 99, 71, 106, 102, 43, 101, 105, 78, 108],
+];
+
+/// The offsets of the bits in a pattern of the Moore neighborhood
+const MOORE_OFFSETS: [(isize, isize); 8] = [
+    (-1, -1), (0, -1), (1, -1), (-1, 0), (1, 0), (-1, 1), (0, 1), (1, 1),
+];
+
+/// The number of distinct configurations of n alive neighbors, counting
+/// configurations that are rotations or reflections of each other once
+fn letter_count(n: usize) -> usize {
+    HENSEL_CONFIGURATIONS[n.min(8 - n)].len().max(1)
+}
+
+/// Every rotation and reflection of a pattern of the Moore neighborhood
+fn symmetries(pattern: u8) -> Vec<u8> {
+    let transform = |f: &dyn Fn(isize, isize) -> (isize, isize)| {
+        MOORE_OFFSETS.iter().enumerate()
+            .filter(|&(i, _)| { pattern & (1 << i) != 0 })
+            .fold(0, |acc, (_, &(dx, dy))| {
+                let moved = f(dx, dy);
+                let j = MOORE_OFFSETS.iter().position(|&o| { o == moved })
+                    .expect("symmetries keep offsets in the neighborhood");
+                acc | 1 << j
+            })
+    };
+    vec![
+        transform(&|x, y| { (x, y) }),
+        transform(&|x, y| { (-y, x) }),
+        transform(&|x, y| { (-x, -y) }),
+        transform(&|x, y| { (y, -x) }),
+        transform(&|x, y| { (-x, y) }),
+        transform(&|x, y| { (y, x) }),
+        transform(&|x, y| { (x, -y) }),
+        transform(&|x, y| { (-y, -x) }),
+    ]
+}
+
+/// The index in HENSEL_LETTERS of the letter describing each pattern of alive
+/// neighbors. Patterns of 0 or 8 neighbors have no letter and get 0
+fn hensel_letters() -> Vec<u8> {
+    let mut letters = vec![0; 256];
+    for n in 1..8 {
+        for (letter, &golly) in HENSEL_CONFIGURATIONS[n.min(8 - n)].iter()
+            .enumerate() {
+            // drop the middle bit
+            let pattern = ((golly & 0xf) | ((golly >> 5) << 4)) as u8;
+            let pattern = if n > 4 { !pattern } else { pattern };
+            for symmetry in symmetries(pattern) {
+                letters[symmetry as usize] = letter as u8;
+            }
+        }
+    }
+    letters
+}
+
+/// A rule over the states of Conway's Game of Life where whether a square is
+/// alive next depends on the configuration of its alive neighbors up to
+/// rotation and reflection, rather than just how many there are. The
+/// configurations are named with Hensel notation, e.g. "B2-a/S12"
+#[derive(Clone, Debug, PartialEq)]
+pub struct Isotropic {
+    name: String,
+    /// for each count of alive neighbors, bit k is set if the configuration
+    /// with the k-th letter causes a birth
+    birth: [u16; 9],
+    /// for each count of alive neighbors, bit k is set if the configuration
+    /// with the k-th letter causes survival
+    survival: [u16; 9],
+    /// whether a square is alive next indexed by its pattern of alive
+    /// neighbors, plus 256 if it is alive now
+    table: Vec<bool>,
+}
+
+impl Isotropic {
+    fn from_conditions(birth: [u16; 9], survival: [u16; 9]) -> Isotropic {
+        let letters = hensel_letters();
+        let table = (0..512usize).map(|i| {
+            let pattern = i & 0xff;
+            let n = pattern.count_ones() as usize;
+            let conditions = if i >= 256 { &survival } else { &birth };
+            conditions[n] & (1 << letters[pattern]) != 0
+        }).collect();
+        let mut rule = Isotropic {
+            name: String::new(),
+            birth,
+            survival,
+            table,
+        };
+        rule.name = rule.rulestring();
+        rule
+    }
+
+    /// The rule in ISOTROPIC_CATALOG with a name like "tlife"
+    pub fn named(name: &str) -> Option<Isotropic> {
+        find_named(ISOTROPIC_CATALOG, name).map(|(n, rule)| {
+            Isotropic { name: n.to_string(), ..rule }
+        })
+    }
+
+    /// The rule in Hensel notation, e.g. "B2-a/S12"
+    pub fn rulestring(&self) -> String {
+        format!("B{}/S{}", format_hensel(&self.birth),
+                format_hensel(&self.survival))
+    }
+
+    /// The next state of a square that is currently in state curr where
+    /// pattern is the bit set of its alive neighbors in the order of the
+    /// offsets of the Moore neighborhood
+    pub fn next_state(&self, curr: State, pattern: u8) -> State {
+        let alive = curr == State::Alive;
+        if self.table[(alive as usize) << 8 | pattern as usize] {
+            State::Alive
+        } else {
+            State::Dead
+        }
+    }
+}
+
+/// Parse a condition in Hensel notation like "2-a34q" into the letters that
+/// are included for each count
+fn parse_hensel(rulestring: &str, condition: &str)
+    -> Result<[u16; 9], ParseRuleError> {
+    let mut conditions = [0; 9];
+    let mut chars = condition.chars().peekable();
+    while let Some(c) = chars.next() {
+        let n = c.to_digit(10).filter(|&n| { n <= 8 }).ok_or_else(|| {
+            ParseRuleError::new(
+                rulestring,
+                format!("{:?} is not a neighbor count between 0 and 8", c))
+        })? as usize;
+        let negated = chars.peek() == Some(&'-');
+        if negated {
+            chars.next();
+        }
+
+        let mut letters = 0;
+        while let Some(&l) = chars.peek().filter(|l| { l.is_alphabetic() }) {
+            chars.next();
+            let k = HENSEL_LETTERS.find(l.to_ascii_lowercase())
+                .filter(|&k| { k < HENSEL_CONFIGURATIONS[n.min(8 - n)].len() })
+                .ok_or_else(|| {
+                    ParseRuleError::new(
+                        rulestring,
+                        format!("{:?} is not a configuration of {} neighbors",
+                                l, n))
+                })?;
+            letters |= 1 << k;
+        }
+
+        let all = (1 << letter_count(n)) - 1;
+        conditions[n] |= match (negated, letters) {
+            (false, 0) => all,
+            (true, _) => all & !letters,
+            (false, _) => letters,
+        };
+    }
+    Ok(conditions)
+}
+
+/// Write the letters included for each count in Hensel notation, using
+/// whichever of listing or excluding letters is shorter
+fn format_hensel(conditions: &[u16; 9]) -> String {
+    let mut s = String::new();
+    for (n, &letters) in conditions.iter().enumerate() {
+        if letters == 0 {
+            continue;
+        }
+        s.push_str(&n.to_string());
+
+        let all = (1 << letter_count(n)) - 1;
+        if letters != all {
+            let list = |letters: u16| -> String {
+                HENSEL_LETTERS.chars().enumerate()
+                    .filter(|&(k, _)| { letters & (1 << k) != 0 })
+                    .map(|(_, l)| { l })
+                    .collect()
+            };
+            let (included, excluded) = (list(letters), list(all & !letters));
+            if included.len() <= excluded.len() {
+                s.push_str(&included);
+            } else {
+                s.push('-');
+                s.push_str(&excluded);
+            }
+        }
+    }
+    s
+}
+
+impl FromStr for Isotropic {
+    type Err = ParseRuleError;
+
+    /// Parse a rulestring in Hensel notation ("B2-a/S12") or the name of a
+    /// rule in the catalog. Outer totalistic rulestrings are also accepted
+    fn from_str(rulestring: &str) -> Result<Isotropic, ParseRuleError> {
+        if let Some(rule) = Isotropic::named(rulestring) {
+            return Ok(rule);
+        }
+
+        let (birth, survival) = split_birth_survival(rulestring)?;
+        Ok(Isotropic::from_conditions(parse_hensel(rulestring, birth)?,
+                                      parse_hensel(rulestring, survival)?))
+    }
+}
+
+impl fmt::Display for Isotropic {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.rulestring())
+    }
+}
+
+impl Rule for Isotropic {
+    type State = State;
+
+    fn name(&self) -> &str { &self.name }
+    fn state_count(&self) -> Option<usize> { Some(2) }
+
+    fn step(&self, curr: Square<State>) -> State {
+        let pattern = curr.neighbor_pattern(&MOORE, |e| { *e == State::Alive });
+        self.next_state(*curr.get(0, 0), pattern as u8)
+    }
+}
+
 #[cfg(test)]
 mod tests {
-    use super::super::Frame;
-    use super::rule;
+    use super::super::{Boundary, Frame, Neighborhood, Rule};
+    use super::{rule, GameOfLife, LifeLike, CATALOG};
+    use super::{Isotropic, ISOTROPIC_CATALOG, HENSEL_CONFIGURATIONS};
+    use super::{letter_count, symmetries};
     use super::State;
     use super::State::Alive;
 
@@ -72,6 +571,36 @@ mod tests {
         assert_eq!(frame3, frame1);
     }
 
+    #[test]
+    fn oscillator_finite() {
+        // The same oscillator on a board with dead edges doesn't need padding
+        let mut frame1 = Frame::<State>::new_finite(3, 3);
+        *frame1.get_mut(1, 0) = Alive;
+        *frame1.get_mut(1, 1) = Alive;
+        *frame1.get_mut(1, 2) = Alive;
+
+        let frame2 = frame1.next_frame(rule);
+        let frame3 = frame2.next_frame(rule);
+
+        let mut expected = Frame::<State>::new_finite(3, 3);
+        *expected.get_mut(0, 1) = Alive;
+        *expected.get_mut(1, 1) = Alive;
+        *expected.get_mut(2, 1) = Alive;
+
+        assert_eq!(frame2, expected);
+        assert_eq!(frame3, frame1);
+    }
+
+    #[test]
+    fn as_rule() {
+        let mut frame = Frame::<State>::new(4, 4);
+        *frame.get_mut(1, 0) = Alive;
+        *frame.get_mut(1, 1) = Alive;
+        *frame.get_mut(1, 2) = Alive;
+
+        assert_eq!(frame.advance(&GameOfLife), frame.next_frame(rule));
+    }
+
     #[test]
     fn glider() {
         // Create a glider as pictured and follow its transformations:
@@ -112,4 +641,205 @@ mod tests {
 
         assert_eq!(frame, frame_new);
     }
+
+    #[test]
+    fn parse() {
+        let highlife = "B36/S23".parse::<LifeLike>().unwrap();
+        assert_eq!(highlife, LifeLike::new(&[3, 6], &[2, 3]));
+        assert_eq!("23/36".parse::<LifeLike>().unwrap().rulestring(), "B36/S23");
+        assert_eq!("b2/s".parse::<LifeLike>().unwrap().rulestring(), "B2/S");
+        assert_eq!("B3/S23".parse::<LifeLike>().unwrap(), LifeLike::conway());
+        assert!("B9/S23".parse::<LifeLike>().is_err());
+        assert!("B3S23".parse::<LifeLike>().is_err());
+
+        let hex = "B2/S34H".parse::<LifeLike>().unwrap();
+        assert_eq!(hex, LifeLike::hexagonal(&[2], &[3, 4]));
+        assert_eq!(hex.rulestring(), "B2/S34H");
+        assert_eq!(hex.neighborhood(), Neighborhood::Hexagonal(1));
+        assert!("B7/S34H".parse::<LifeLike>().is_err());
+
+        let tri = "B4/S3abT".parse::<LifeLike>().unwrap();
+        assert_eq!(tri, LifeLike::triangular(Neighborhood::Triangular, &[4],
+                                             &[3, 10, 11]));
+        assert_eq!(tri.rulestring(), "B4/S3abT");
+        assert_eq!("b1/ste".parse::<LifeLike>().unwrap().neighborhood(),
+                   Neighborhood::TriangularEdges);
+        assert_eq!("B3/S2TV".parse::<LifeLike>().unwrap().rulestring(),
+                   "B3/S2TV");
+        assert!("B3/S2aTV".parse::<LifeLike>().is_err());
+        assert!("B3/S2a".parse::<LifeLike>().is_err());
+    }
+
+    #[test]
+    #[should_panic(expected = "between 0 and 8")]
+    fn counts_past_neighborhood() {
+        LifeLike::new(&[3], &[2, 40]);
+    }
+
+    #[test]
+    fn catalog() {
+        for &(name, rulestring) in CATALOG {
+            let rule = LifeLike::named(name).unwrap();
+            assert_eq!(rule.name(), name);
+            assert_eq!(rule.rulestring(), rulestring);
+        }
+        assert_eq!("highlife".parse::<LifeLike>().unwrap().rulestring(),
+                   "B36/S23");
+        assert_eq!(LifeLike::named("not a rule"), None);
+    }
+
+    #[test]
+    fn life_like_matches_rule() {
+        let mut frame = Frame::<State>::new(6, 6);
+        *frame.get_mut(1, 0) = Alive;
+        *frame.get_mut(2, 1) = Alive;
+        *frame.get_mut(0, 2) = Alive;
+        *frame.get_mut(1, 2) = Alive;
+        *frame.get_mut(2, 2) = Alive;
+
+        let conway = LifeLike::conway();
+        let mut expected = frame.next_frame(rule);
+        frame = frame.advance(&conway);
+        for _ in 0..10 {
+            assert_eq!(frame, expected);
+            expected = expected.next_frame(rule);
+            frame = frame.advance(&conway);
+        }
+    }
+
+    #[test]
+    fn seeds() {
+        // in Seeds every alive square dies and two adjacent squares give birth
+        // to the squares on either side of them
+        let mut frame = Frame::<State>::new_finite(4, 3);
+        *frame.get_mut(1, 1) = Alive;
+        *frame.get_mut(2, 1) = Alive;
+
+        let next = frame.advance(&LifeLike::named("Seeds").unwrap());
+
+        let mut expected = Frame::<State>::new_finite(4, 3);
+        *expected.get_mut(1, 0) = Alive;
+        *expected.get_mut(2, 0) = Alive;
+        *expected.get_mut(1, 2) = Alive;
+        *expected.get_mut(2, 2) = Alive;
+        assert_eq!(next, expected);
+    }
+
+    #[test]
+    fn hexagonal() {
+        // in B2/S34H two neighboring hexagons die and give birth to the two
+        // hexagons that neighbor both of them, which do the same in turn
+        let mut frame = Frame::<State>::new(8, 8)
+            .with_boundary(Boundary::Toroidal);
+        *frame.get_mut(3, 3) = Alive;
+        *frame.get_mut(4, 3) = Alive;
+
+        let rule = "B2/S34H".parse::<LifeLike>().unwrap();
+        let next = frame.advance(&rule);
+        let mut expected = Frame::<State>::new(8, 8);
+        *expected.get_mut(4, 2) = Alive;
+        *expected.get_mut(3, 4) = Alive;
+        assert_eq!(next, expected);
+        assert_eq!(next.advance(&rule), frame);
+    }
+
+    #[test]
+    fn triangular() {
+        // with B1/STE a triangle dies and the triangles that share an edge
+        // with it are born, which are the ones to its left and right and the
+        // one across its base, below it if it points up and above otherwise
+        let rule = "B1/STE".parse::<LifeLike>().unwrap();
+        for &(x, y, base) in [(2, 2, 3), (3, 2, 1)].iter() {
+            let mut frame = Frame::<State>::new(6, 6);
+            *frame.get_mut(x, y) = Alive;
+            let mut expected = Frame::<State>::new(6, 6);
+            *expected.get_mut(x - 1, y) = Alive;
+            *expected.get_mut(x + 1, y) = Alive;
+            *expected.get_mut(x, base) = Alive;
+            assert_eq!(frame.advance(&rule), expected);
+        }
+    }
+
+    #[test]
+    fn hensel_configurations() {
+        // the symmetries of the configurations of each count should cover
+        // every pattern with that many alive neighbors exactly once
+        for n in 1..8usize {
+            let mut seen = vec![false; 256];
+            for &golly in HENSEL_CONFIGURATIONS[n.min(8 - n)] {
+                let pattern = ((golly & 0xf) | ((golly >> 5) << 4)) as u8;
+                let pattern = if n > 4 { !pattern } else { pattern };
+                let mut orbit = symmetries(pattern);
+                orbit.sort();
+                orbit.dedup();
+                for p in orbit {
+                    assert_eq!(p.count_ones() as usize, n);
+                    assert!(!seen[p as usize]);
+                    seen[p as usize] = true;
+                }
+            }
+            let total = (0..256u32).filter(|p| { p.count_ones() as usize == n })
+                .count();
+            assert_eq!(seen.iter().filter(|&&s| { s }).count(), total);
+            assert_eq!(letter_count(n), HENSEL_CONFIGURATIONS[n.min(8 - n)].len());
+        }
+    }
+
+    #[test]
+    fn parse_isotropic() {
+        let friends = "B2-a/S12".parse::<Isotropic>().unwrap();
+        assert_eq!(friends.rulestring(), "B2-a/S12");
+        assert_eq!("b2cekin/s12".parse::<Isotropic>().unwrap(), friends);
+        assert_eq!("B3/S2-i34q".parse::<Isotropic>().unwrap().rulestring(),
+                   "B3/S2-i34q");
+        assert_eq!("B2ce/S".parse::<Isotropic>().unwrap().rulestring(),
+                   "B2ce/S");
+        assert!("B2x/S".parse::<Isotropic>().is_err());
+        assert!("B1k/S".parse::<Isotropic>().is_err());
+        assert!("B9/S".parse::<Isotropic>().is_err());
+    }
+
+    #[test]
+    fn isotropic_catalog() {
+        for &(name, rulestring) in ISOTROPIC_CATALOG {
+            let rule = Isotropic::named(name).unwrap();
+            assert_eq!(rule.name(), name);
+            assert_eq!(rule.rulestring(), rulestring);
+        }
+    }
+
+    #[test]
+    fn isotropic_totalistic_is_life() {
+        let mut frame = Frame::<State>::new(6, 6);
+        *frame.get_mut(1, 0) = Alive;
+        *frame.get_mut(2, 1) = Alive;
+        *frame.get_mut(0, 2) = Alive;
+        *frame.get_mut(1, 2) = Alive;
+        *frame.get_mut(2, 2) = Alive;
+        *frame.get_mut(4, 4) = Alive;
+
+        let life = "B3/S23".parse::<Isotropic>().unwrap();
+        for _ in 0..8 {
+            let next = frame.advance(&life);
+            assert_eq!(next, frame.next_frame(rule));
+            frame = next;
+        }
+    }
+
+    #[test]
+    fn configuration_matters() {
+        let friends = Isotropic::named("Just Friends").unwrap();
+
+        // two adjacent neighbors, 2a, don't cause a birth
+        let mut adjacent = Frame::<State>::new_finite(3, 3);
+        *adjacent.get_mut(1, 0) = Alive;
+        *adjacent.get_mut(2, 0) = Alive;
+        assert_eq!(*adjacent.advance(&friends).get(1, 1), State::Dead);
+
+        // two opposite neighbors, 2i, do
+        let mut opposite = Frame::<State>::new_finite(3, 3);
+        *opposite.get_mut(1, 0) = Alive;
+        *opposite.get_mut(1, 2) = Alive;
+        assert_eq!(*opposite.advance(&friends).get(1, 1), Alive);
+    }
 }
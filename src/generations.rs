@@ -0,0 +1,255 @@
+use super::{FiniteState, Rule, Square, MOORE, ParseRuleError};
+use super::rule::{split_birth_survival, parse_counts, format_counts,
+                  count_bits, find_named};
+
+use std::fmt;
+use std::str::FromStr;
+
+/// The state of a node in a Generations rule
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Default)]
+pub enum State {
+    Alive,
+    /// a node that has died and can't be born again yet, along with the number
+    /// of generations it has been dying for
+    Dying(u8),
+    #[default]
+    Dead,
+}
+
+impl FiniteState for State {
+    fn count() -> usize { 258 }
+
+    fn index(&self) -> usize {
+        match *self {
+            State::Dead => 0,
+            State::Alive => 1,
+            State::Dying(age) => 2 + age as usize,
+        }
+    }
+
+    fn from_index(index: usize) -> State {
+        match index {
+            0 => State::Dead,
+            1 => State::Alive,
+            _ => State::Dying((index - 2) as u8),
+        }
+    }
+}
+
+/// Well known Generations rules and their rulestrings
+pub const CATALOG: &[(&str, &str)] = &[
+    ("Brian's Brain", "B2/S/C3"),
+    ("Star Wars", "B2/S345/C4"),
+    ("Frogs", "B34/S12/C3"),
+    ("Sticks", "B2/S3456/C6"),
+    ("Spirals", "B234/S2/C5"),
+    ("Transers", "B26/S345/C5"),
+    ("Bloomerang", "B34678/S234/C24"),
+    ("Belzhab", "B23/S23/C8"),
+    ("Fireworks", "B13/S2/C21"),
+];
+
+/// A Life-like rule where squares that die pass through a number of dying
+/// states before they are dead and can be born again
+#[derive(Clone, Debug, PartialEq)]
+pub struct Generations {
+    name: String,
+    /// bit n is set if a dead square with n alive neighbors becomes alive
+    birth: u32,
+    /// bit n is set if an alive square with n alive neighbors stays alive
+    survival: u32,
+    /// the total number of states including alive and dead
+    states: u8,
+}
+
+impl Generations {
+    /// Create a rule from the neighbor counts that cause birth and survival and
+    /// the total number of states, which must be at least 2. Panics if a count
+    /// is larger than 8
+    pub fn new(birth: &[usize], survival: &[usize], states: u8) -> Generations {
+        assert!(states >= 2, "a Generations rule needs at least 2 states");
+        assert!(birth.iter().chain(survival).all(|&n| { n <= 8 }),
+                "neighbor counts must be between 0 and 8");
+        let mut rule = Generations {
+            name: String::new(),
+            birth: count_bits(birth),
+            survival: count_bits(survival),
+            states,
+        };
+        rule.name = rule.rulestring();
+        rule
+    }
+
+    /// The rule in CATALOG with a name like "Brian's Brain"
+    pub fn named(name: &str) -> Option<Generations> {
+        find_named(CATALOG, name).map(|(n, rule)| {
+            Generations { name: n.to_string(), ..rule }
+        })
+    }
+
+    /// The rule in B/S/C notation, e.g. "B2/S/C3"
+    pub fn rulestring(&self) -> String {
+        format!("B{}/S{}/C{}", format_counts(self.birth),
+                format_counts(self.survival), self.states)
+    }
+
+    /// The total number of states including alive and dead
+    pub fn states(&self) -> u8 {
+        self.states
+    }
+
+    /// The next state of a square that is currently in state curr and has n
+    /// alive neighbors
+    pub fn next_state(&self, curr: State, n: usize) -> State {
+        use self::State::{Alive, Dying, Dead};
+        // the dying states are numbered 1 to states - 2
+        let decay = |age: u8| {
+            if age < self.states - 2 { Dying(age + 1) } else { Dead }
+        };
+        let has = |counts: u32| {
+            counts.checked_shr(n as u32).unwrap_or(0) & 1 != 0
+        };
+
+        match curr {
+            Alive if has(self.survival) => Alive,
+            Alive => decay(0),
+            Dying(age) => decay(age),
+            Dead if has(self.birth) => Alive,
+            Dead => Dead,
+        }
+    }
+}
+
+impl FromStr for Generations {
+    type Err = ParseRuleError;
+
+    /// Parse a rulestring in B/S/C notation ("B2/S/C3"), Golly's S/B/C notation
+    /// ("/2/3") or the name of a rule in the catalog
+    fn from_str(rulestring: &str) -> Result<Generations, ParseRuleError> {
+        if let Some(rule) = Generations::named(rulestring) {
+            return Ok(rule);
+        }
+
+        let split = rulestring.rfind('/').ok_or_else(|| {
+            ParseRuleError::new(rulestring, "expected a number of states")
+        })?;
+        let (bs, states) = (&rulestring[..split], rulestring[split + 1..].trim());
+        let states = states.trim_start_matches(['C', 'c'])
+            .parse::<u8>()
+            .ok()
+            .filter(|&states| { states >= 2 })
+            .ok_or_else(|| {
+                ParseRuleError::new(
+                    rulestring, "the number of states must be between 2 and 255")
+            })?;
+
+        let (birth, survival) = split_birth_survival(bs)
+            .map_err(|_| {
+                ParseRuleError::new(
+                    rulestring, "expected a birth and a survival condition")
+            })?;
+        let mut rule = Generations {
+            name: String::new(),
+            birth: parse_counts(rulestring, birth, 8)?,
+            survival: parse_counts(rulestring, survival, 8)?,
+            states,
+        };
+        rule.name = rule.rulestring();
+        Ok(rule)
+    }
+}
+
+impl fmt::Display for Generations {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.rulestring())
+    }
+}
+
+impl Rule for Generations {
+    type State = State;
+
+    fn name(&self) -> &str { &self.name }
+    fn state_count(&self) -> Option<usize> { Some(self.states as usize) }
+
+    fn step(&self, curr: Square<State>) -> State {
+        let n = curr.count_neighbors(&MOORE, |e| { *e == State::Alive });
+        self.next_state(*curr.get(0, 0), n)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::{Frame, Rule};
+    use super::{Generations, CATALOG};
+    use super::State;
+    use super::State::{Alive, Dying, Dead};
+
+    #[test]
+    fn parse() {
+        let brain = Generations::new(&[2], &[], 3);
+        assert_eq!("B2/S/C3".parse::<Generations>().unwrap(), brain);
+        assert_eq!("/2/3".parse::<Generations>().unwrap(), brain);
+        assert_eq!("b2/s/3".parse::<Generations>().unwrap(), brain);
+        assert_eq!("345/2/4".parse::<Generations>().unwrap().rulestring(),
+                   "B2/S345/C4");
+        assert!("B2/S".parse::<Generations>().is_err());
+        assert!("B2/S/C1".parse::<Generations>().is_err());
+        assert!("B2/S/Cx".parse::<Generations>().is_err());
+        assert!("B9/S/C3".parse::<Generations>().is_err());
+    }
+
+    #[test]
+    fn catalog() {
+        for &(name, rulestring) in CATALOG {
+            let rule = Generations::named(name).unwrap();
+            assert_eq!(rule.name(), name);
+            assert_eq!(rule.rulestring(), rulestring);
+        }
+    }
+
+    #[test]
+    fn decay() {
+        let rule = Generations::new(&[3], &[2, 3], 4);
+        assert_eq!(rule.next_state(Alive, 2), Alive);
+        assert_eq!(rule.next_state(Alive, 1), Dying(1));
+        assert_eq!(rule.next_state(Dying(1), 3), Dying(2));
+        assert_eq!(rule.next_state(Dying(2), 3), Dead);
+        assert_eq!(rule.next_state(Dead, 3), Alive);
+
+        // with two states there are no dying states, it is just Life
+        let life = Generations::new(&[3], &[2, 3], 2);
+        assert_eq!(life.next_state(Alive, 1), Dead);
+        assert_eq!(life.next_state(Alive, 40), Dead);
+
+        // the most states there can be, and dying states past the last one
+        let long = "B3/S23/C255".parse::<Generations>().unwrap();
+        assert_eq!(long.next_state(Dying(252), 0), Dying(253));
+        assert_eq!(long.next_state(Dying(253), 0), Dead);
+        assert_eq!(long.next_state(Dying(255), 0), Dead);
+        assert!("B3/S23/C256".parse::<Generations>().is_err());
+    }
+
+    #[test]
+    fn brians_brain() {
+        // two alive squares give birth on either side and then fade away
+        let rule = Generations::named("Brian's Brain").unwrap();
+        let mut frame = Frame::<State>::new_finite(4, 3);
+        *frame.get_mut(1, 1) = Alive;
+        *frame.get_mut(2, 1) = Alive;
+
+        let next = frame.advance(&rule);
+
+        let mut expected = Frame::<State>::new_finite(4, 3);
+        *expected.get_mut(1, 0) = Alive;
+        *expected.get_mut(2, 0) = Alive;
+        *expected.get_mut(1, 2) = Alive;
+        *expected.get_mut(2, 2) = Alive;
+        *expected.get_mut(1, 1) = Dying(1);
+        *expected.get_mut(2, 1) = Dying(1);
+        assert_eq!(next, expected);
+
+        let next = next.advance(&rule);
+        assert_eq!(*next.get(1, 1), Dead);
+        assert_eq!(*next.get(1, 0), Dying(1));
+    }
+}
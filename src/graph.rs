@@ -0,0 +1,450 @@
+use super::{Frame, Neighborhood};
+
+use rand::Rng;
+
+use std::collections::HashMap;
+use std::io::{self, BufRead};
+
+/// How many times Graph::random_regular tries to pair up the ends of the edges
+const RANDOM_REGULAR_TRIES: usize = 1000;
+
+/// A simulation on an arbitrary graph, where every node holds a state and its
+/// neighbors are the nodes in its adjacency list. Lattices, random networks
+/// and tilings loaded from a file are all advanced the same way
+#[derive(Clone, Debug, PartialEq)]
+pub struct Graph<T> {
+    states: Vec<T>,
+    adjacency: Vec<Vec<usize>>,
+}
+
+impl<T> Graph<T>
+where T: Default + Clone {
+    /// Create a graph whose nodes are all in the default state from the
+    /// adjacency list of every node. Panics if a neighbor isn't a node
+    pub fn new(adjacency: Vec<Vec<usize>>) -> Graph<T> {
+        let n = adjacency.len();
+        assert!(adjacency.iter().flatten().all(|&v| { v < n }),
+                "every neighbor must be a node of the graph");
+        Graph { states: vec![T::default(); n], adjacency }
+    }
+
+    /// Create an undirected graph with n nodes from a list of edges
+    pub fn from_edges(n: usize, edges: &[(usize, usize)]) -> Graph<T> {
+        let mut adjacency = vec![Vec::new(); n];
+        for &(u, v) in edges {
+            adjacency[u].push(v);
+            adjacency[v].push(u);
+        }
+        Graph::new(adjacency)
+    }
+
+    /// The graph of the squares of a frame, where square (x, y) is node
+    /// y * width + x with its state, and its neighbors are the squares in the
+    /// neighborhood according to the frame's boundary condition. Points past
+    /// the edges of a frame with a constant boundary aren't nodes, so squares
+    /// along the edges have fewer neighbors, which matches the frame when the
+    /// boundary is the default state. A reflecting boundary maps points past
+    /// the edges back onto squares, so squares along the edges list themselves
+    /// or the same neighbor twice, and the graph isn't simple
+    pub fn from_frame(frame: &Frame<T>, neighborhood: &Neighborhood)
+        -> Graph<T> {
+        let mut adjacency = Vec::with_capacity(frame.width() * frame.height());
+        let mut states = Vec::with_capacity(adjacency.capacity());
+        for (x, y, state) in frame.enumerate_squares() {
+            let flip = neighborhood.is_triangular()
+                && !frame.square(x, y).points_up();
+            let (x, y) = (x as isize, y as isize);
+            adjacency.push(neighborhood.offsets()
+                .filter_map(|(i, j)| {
+                    frame.resolve(x + i, if flip { y - j } else { y + j })
+                })
+                .map(|(x, y)| { y * frame.width() + x })
+                .collect());
+            states.push(state.clone());
+        }
+        Graph { states, adjacency }
+    }
+
+    /// A random graph with n nodes where every node has the given degree.
+    /// Each try pairs up the ends of the edges at random and starts over if the
+    /// last few can't be paired, which is rare unless the degree is close to
+    /// n. Panics if n * degree is odd, the degree isn't less than n or none of
+    /// 1000 tries succeeds
+    pub fn random_regular<G>(n: usize, degree: usize, rng: &mut G) -> Graph<T>
+    where G: Rng {
+        assert!(n * degree % 2 == 0, "n * degree must be even");
+        assert!(degree < n || n == 0, "the degree must be less than n");
+        // pair up random ends of edges that don't make loops or parallel edges
+        // and start over if the last few ends can't be paired
+        for _ in 0..RANDOM_REGULAR_TRIES {
+            let mut adjacency = vec![Vec::new(); n];
+            let mut ends = (0..n)
+                .flat_map(|v| { ::std::iter::repeat(v).take(degree) })
+                .collect::<Vec<_>>();
+            while !ends.is_empty() {
+                let attempts = 10 * ends.len();
+                let pair = (0..attempts).map(|_| {
+                    (rng.gen_range(0, ends.len()), rng.gen_range(0, ends.len()))
+                }).find(|&(i, j)| {
+                    let (u, v) = (ends[i], ends[j]);
+                    u != v && !adjacency[u].contains(&v)
+                });
+                let (i, j) = match pair {
+                    Some(pair) => pair,
+                    None => break,
+                };
+                let (u, v) = (ends[i], ends[j]);
+                adjacency[u].push(v);
+                adjacency[v].push(u);
+                ends.swap_remove(i.max(j));
+                ends.swap_remove(i.min(j));
+            }
+            if ends.is_empty() {
+                return Graph::new(adjacency);
+            }
+        }
+        panic!("no {}-regular graph with {} nodes was found in {} tries",
+               degree, n, RANDOM_REGULAR_TRIES);
+    }
+
+    /// A Watts-Strogatz small-world network, a ring of n nodes that are each
+    /// connected to the k nearest nodes, where every edge is moved to a random
+    /// node with probability p. Panics if k is odd or not less than n
+    pub fn small_world<G>(n: usize, k: usize, p: f64, rng: &mut G) -> Graph<T>
+    where G: Rng {
+        assert!(k % 2 == 0 && (k < n || n == 0),
+                "k must be even and less than n");
+        let mut adjacency = vec![Vec::new(); n];
+        for u in 0..n {
+            for j in 1..k / 2 + 1 {
+                let v = (u + j) % n;
+                adjacency[u].push(v);
+                adjacency[v].push(u);
+            }
+        }
+        for u in 0..n {
+            for j in 1..k / 2 + 1 {
+                let v = (u + j) % n;
+                if rng.gen::<f64>() >= p || adjacency[u].len() == n - 1 {
+                    continue;
+                }
+                let w = loop {
+                    let w = rng.gen_range(0, n);
+                    if w != u && !adjacency[u].contains(&w) {
+                        break w;
+                    }
+                };
+                if let Some(i) = adjacency[u].iter().position(|&e| { e == v }) {
+                    adjacency[u][i] = w;
+                    adjacency[v].retain(|&e| { e != u });
+                    adjacency[w].push(u);
+                }
+            }
+        }
+        Graph::new(adjacency)
+    }
+
+    /// The vertices and edges of a geodesic sphere, an icosahedron whose
+    /// triangles are split into four the given number of times. The 12
+    /// corners of the icosahedron have 5 neighbors and every other node has 6
+    pub fn geodesic_sphere(subdivisions: usize) -> Graph<T> {
+        let mut faces: Vec<[usize; 3]> = vec![
+            [0, 11, 5], [0, 5, 1], [0, 1, 7], [0, 7, 10], [0, 10, 11],
+            [1, 5, 9], [5, 11, 4], [11, 10, 2], [10, 7, 6], [7, 1, 8],
+            [3, 9, 4], [3, 4, 2], [3, 2, 6], [3, 6, 8], [3, 8, 9],
+            [4, 9, 5], [2, 4, 11], [6, 2, 10], [8, 6, 7], [9, 8, 1],
+        ];
+        let mut n = 12;
+        for _ in 0..subdivisions {
+            let mut midpoints = HashMap::new();
+            let mut midpoint = |a: usize, b: usize| {
+                *midpoints.entry((a.min(b), a.max(b))).or_insert_with(|| {
+                    n += 1;
+                    n - 1
+                })
+            };
+            faces = faces.iter().flat_map(|&[a, b, c]| {
+                let (ab, bc, ca) = (midpoint(a, b), midpoint(b, c),
+                                    midpoint(c, a));
+                vec![[a, ab, ca], [b, bc, ab], [c, ca, bc], [ab, bc, ca]]
+            }).collect();
+        }
+        // every edge is shared by two faces, which list it in opposite
+        // directions, so each direction only adds one neighbor
+        let mut adjacency = vec![Vec::new(); n];
+        for &[a, b, c] in faces.iter() {
+            adjacency[a].push(b);
+            adjacency[b].push(c);
+            adjacency[c].push(a);
+        }
+        Graph::new(adjacency)
+    }
+
+    /// Read an undirected graph from a list of edges with one edge per line,
+    /// written as the indices of its two nodes separated by whitespace. Empty
+    /// lines and lines starting with # are skipped, and the graph has as many
+    /// nodes as the largest index needs
+    pub fn read_edges<R>(reader: R) -> io::Result<Graph<T>>
+    where R: BufRead {
+        let mut edges = Vec::new();
+        for (number, line) in reader.lines().enumerate() {
+            let line = line?;
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let nodes = line.split_whitespace()
+                .map(|s| { s.parse::<usize>().ok() })
+                .collect::<Option<Vec<_>>>()
+                .filter(|nodes| { nodes.len() == 2 });
+            match nodes {
+                Some(nodes) => edges.push((nodes[0], nodes[1])),
+                None => return Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("line {} is not an edge: {:?}", number + 1, line))),
+            }
+        }
+        let n = edges.iter().map(|&(u, v)| { u.max(v) + 1 }).max().unwrap_or(0);
+        Ok(Graph::from_edges(n, &edges))
+    }
+}
+
+/// Getters / setters for the data in the graph
+impl<T> Graph<T> {
+    /// the number of nodes
+    pub fn len(&self) -> usize {
+        self.states.len()
+    }
+
+    /// whether the graph has no nodes
+    pub fn is_empty(&self) -> bool {
+        self.states.is_empty()
+    }
+
+    /// the state of node i
+    pub fn get(&self, i: usize) -> &T {
+        &self.states[i]
+    }
+
+    /// get a mutable reference to the state of node i
+    pub fn get_mut(&mut self, i: usize) -> &mut T {
+        &mut self.states[i]
+    }
+
+    /// the states of every node
+    pub fn states(&self) -> &[T] {
+        &self.states
+    }
+
+    /// the neighbors of node i
+    pub fn neighbors(&self, i: usize) -> &[usize] {
+        &self.adjacency[i]
+    }
+
+    /// node i, as seen by a step function
+    pub fn node(&self, i: usize) -> Node<'_, T> {
+        Node { graph: self, index: i }
+    }
+}
+
+impl<T> Graph<T>
+where T: Clone {
+    /// return the next generation of the graph, computing the state of every
+    /// node with a step function like Frame::next_frame
+    pub fn next_graph<F>(&self, step: F) -> Graph<T>
+    where F: Fn(Node<T>) -> T {
+        Graph {
+            states: (0..self.len()).map(|i| { step(self.node(i)) }).collect(),
+            adjacency: self.adjacency.clone(),
+        }
+    }
+
+    /// return the next generation of the graph advancing each node with a rule
+    pub fn advance<R>(&self, rule: &R) -> Graph<T>
+    where R: GraphRule<State = T> + ?Sized {
+        self.next_graph(|node| { rule.step(node) })
+    }
+}
+
+/// Represents a single node in the graph
+#[derive(Debug, PartialEq)]
+pub struct Node<'a, T>
+where T: 'a {
+    graph: &'a Graph<T>,
+    index: usize,
+}
+
+impl<'a, T> Node<'a, T>
+where T: 'a {
+    /// The state of the node
+    pub fn get(&self) -> &'a T {
+        self.graph.get(self.index)
+    }
+
+    /// The index of the node in the graph
+    pub fn index(&self) -> usize {
+        self.index
+    }
+
+    /// The number of neighbors
+    pub fn degree(&self) -> usize {
+        self.graph.neighbors(self.index).len()
+    }
+
+    /// Iterate over the states of the node's neighbors
+    pub fn neighbors(&self) -> impl Iterator<Item = &'a T> + 'a {
+        let graph = self.graph;
+        graph.neighbors(self.index).iter().map(move |&i| { graph.get(i) })
+    }
+
+    /// The number of neighbors that satisfy a predicate
+    pub fn count_neighbors<P>(&self, pred: P) -> usize
+    where P: Fn(&T) -> bool {
+        self.neighbors().filter(|e| { pred(e) }).count()
+    }
+
+    /// The graph that the node is in
+    pub fn graph(&self) -> &'a Graph<T> {
+        self.graph
+    }
+}
+
+/// A rule that computes the next state of a node from its neighbors
+pub trait GraphRule {
+    /// The state of a single node
+    type State;
+
+    /// A human readable name for the rule
+    fn name(&self) -> &str;
+
+    /// Compute the next state of a node
+    fn step(&self, node: Node<Self::State>) -> Self::State;
+}
+
+impl<R> GraphRule for &R
+where R: GraphRule + ?Sized {
+    type State = R::State;
+
+    fn name(&self) -> &str { (**self).name() }
+
+    fn step(&self, node: Node<Self::State>) -> Self::State {
+        (**self).step(node)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::{Boundary, Context, Neighborhood, Rule, MOORE};
+    use super::super::frame::tests::scattered;
+    use super::super::game_of_life::{GameOfLife, LifeLike, State};
+    use super::super::game_of_life::State::Alive;
+    use super::Graph;
+
+    /// Whether every edge of a graph goes both ways with no loops or
+    /// parallel edges
+    fn is_simple<T>(graph: &Graph<T>) -> bool {
+        (0..graph.len()).all(|u| {
+            let neighbors = graph.neighbors(u);
+            neighbors.iter().enumerate().all(|(i, &v)| {
+                v != u && !neighbors[..i].contains(&v)
+                    && graph.neighbors(v).contains(&u)
+            })
+        })
+    }
+
+    #[test]
+    fn lattice() {
+        let rules = ["B3/S23", "B36/S23", "B2/S34H", "B45/S34T"];
+        let boundaries = [Boundary::Toroidal, Boundary::Constant(State::Dead),
+                          Boundary::Reflecting];
+        for rulestring in rules.iter() {
+            let rule = rulestring.parse::<LifeLike>().unwrap();
+            for boundary in boundaries.iter() {
+                let mut frame = scattered(12, 8, boundary.clone());
+                let mut graph = Graph::from_frame(&frame, &rule.neighborhood());
+                for _ in 0..4 {
+                    frame = frame.advance(&rule);
+                    graph = graph.advance(&rule);
+                    assert_eq!(graph.states(), &frame.enumerate_squares()
+                               .map(|(_, _, &e)| { e })
+                               .collect::<Vec<_>>()[..]);
+                }
+            }
+        }
+
+        let frame = scattered(10, 10, Boundary::Toroidal);
+        let graph = Graph::from_frame(&frame, &MOORE);
+        assert_eq!(graph.advance(&GameOfLife),
+                   graph.advance(&LifeLike::conway()));
+        assert_eq!(graph.node(0).degree(), 8);
+        let finite = Graph::from_frame(&frame.with_boundary(
+            Boundary::Constant(State::Dead)), &Neighborhood::VonNeumann(1));
+        assert_eq!(finite.node(0).degree(), 2);
+        assert!(is_simple(&graph) && is_simple(&finite));
+
+        // the corner sees itself past both edges
+        let mirrored = Graph::from_frame(
+            &scattered(10, 10, Boundary::Reflecting), &MOORE);
+        assert!(mirrored.neighbors(0).contains(&0));
+        assert!(!is_simple(&mirrored));
+    }
+
+    #[test]
+    fn random_regular() {
+        let mut rng = Context::new(4).rng(0, 0);
+        for &(n, degree) in [(20, 3), (50, 8), (9, 8), (0, 0)].iter() {
+            let graph = Graph::<State>::random_regular(n, degree, &mut rng);
+            assert_eq!(graph.len(), n);
+            assert!((0..n).all(|i| { graph.node(i).degree() == degree }));
+            assert!(is_simple(&graph));
+        }
+    }
+
+    #[test]
+    fn small_world() {
+        let mut rng = Context::new(4).rng(0, 0);
+        let ring = Graph::<State>::small_world(30, 4, 0.0, &mut rng);
+        assert!((0..30).all(|i| { ring.node(i).degree() == 4 }));
+        assert!(ring.neighbors(0).contains(&29));
+        assert!(ring.neighbors(0).contains(&2));
+
+        let graph = Graph::<State>::small_world(100, 6, 0.2, &mut rng);
+        assert!(is_simple(&graph));
+        let edges = (0..100).map(|i| { graph.node(i).degree() }).sum::<usize>();
+        assert_eq!(edges, 600);
+        assert!(graph != Graph::small_world(100, 6, 0.0, &mut rng));
+    }
+
+    #[test]
+    fn geodesic_sphere() {
+        for subdivisions in 0..4 {
+            let graph = Graph::<State>::geodesic_sphere(subdivisions);
+            assert_eq!(graph.len(), 10 * 4usize.pow(subdivisions as u32) + 2);
+            assert!(is_simple(&graph));
+            let degrees = (0..graph.len()).map(|i| { graph.node(i).degree() });
+            assert_eq!(degrees.clone().filter(|&d| { d == 5 }).count(), 12);
+            assert!(degrees.skip(12).all(|d| { d == 6 }));
+        }
+    }
+
+    #[test]
+    fn read_edges() {
+        let text = "# a square with a diagonal\n0 1\n1 2\n\n2 3\n 3 0 \n0 2\n";
+        let mut graph = Graph::<State>::read_edges(text.as_bytes()).unwrap();
+        assert_eq!(graph.len(), 4);
+        assert_eq!(graph.neighbors(0), &[1, 3, 2]);
+        assert!(is_simple(&graph));
+
+        // the node with three alive neighbors is born, the one with two
+        // survives and the ones with one die
+        *graph.get_mut(1) = Alive;
+        *graph.get_mut(2) = Alive;
+        *graph.get_mut(3) = Alive;
+        let next = graph.advance(&GameOfLife);
+        assert_eq!(next.states(), &[Alive, State::Dead, Alive, State::Dead]);
+        assert_eq!(next.node(0).count_neighbors(|&e| { e == Alive }), 1);
+
+        assert!(Graph::<State>::read_edges("0 1\n1\n".as_bytes()).is_err());
+        assert!(Graph::<State>::read_edges("0 x\n".as_bytes()).is_err());
+    }
+}
@@ -0,0 +1,554 @@
+use super::{Frame, Rule};
+use super::game_of_life::{LifeLike, State};
+
+use std::collections::HashMap;
+
+/// Index of a node in the universe
+type NodeId = usize;
+
+/// The dead and alive leaves are always the first two nodes
+const DEAD: NodeId = 0;
+const ALIVE: NodeId = 1;
+
+/// The level of the largest root, whose corners still have coordinates that
+/// fit in an i64
+const MAX_LEVEL: u8 = 62;
+
+/// The largest k that step_pow2 can take, since the root must be 3 levels
+/// above it
+pub const MAX_STEP: u8 = MAX_LEVEL - 3;
+
+/// A square of 2^level by 2^level squares made of four quadrants of the level
+/// below. Leaves have level 0 and are a single square
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+struct Node {
+    level: u8,
+    /// the north west, north east, south west and south east quadrants
+    children: [NodeId; 4],
+    population: u64,
+}
+
+/// An unbounded plane of squares, all dead except for a finite pattern,
+/// advanced with a Life-like rule using the HashLife algorithm. Identical
+/// quadrants are stored once and the future of each quadrant is memoized, which
+/// makes it possible to jump 2^k generations at once for patterns with a lot of
+/// repetition in space and time. Coordinates are i64s, and patterns that grow
+/// past 2^61 squares from where they started panic
+#[derive(Clone, Debug)]
+pub struct HashLife {
+    rule: LifeLike,
+    nodes: Vec<Node>,
+    /// the node made of each combination of quadrants
+    index: HashMap<[NodeId; 4], NodeId>,
+    /// the center of a node advanced 2^k generations, keyed by the node and k
+    results: HashMap<(NodeId, u8), NodeId>,
+    /// the empty node of each level
+    empty: Vec<NodeId>,
+    root: NodeId,
+    /// the coordinate of the top left corner of the root
+    origin: (i64, i64),
+    generation: u64,
+}
+
+impl HashLife {
+    /// Create an empty plane that advances with a rule. Rules where squares
+    /// with no alive neighbors are born (B0) aren't supported because they
+    /// would fill the unbounded plane, and neither are rules on a triangular
+    /// lattice
+    pub fn new(rule: LifeLike) -> HashLife {
+        assert!(!rule.is_birth(0), "HashLife can't simulate rules with B0");
+        assert!(!rule.neighborhood().is_triangular(),
+                "HashLife can't simulate rules on a triangular lattice");
+        let leaf = |population| {
+            Node { level: 0, children: [DEAD; 4], population }
+        };
+        let mut life = HashLife {
+            rule,
+            nodes: vec![leaf(0), leaf(1)],
+            index: HashMap::new(),
+            results: HashMap::new(),
+            empty: vec![DEAD],
+            root: DEAD,
+            origin: (0, 0),
+            generation: 0,
+        };
+        life.root = life.empty_node(3);
+        life
+    }
+
+    /// Create a plane with the squares of a frame with its top left corner at
+    /// (0, 0). The frame's boundary condition is ignored, everything outside of
+    /// it is dead
+    pub fn from_frame(frame: &Frame<State>, rule: LifeLike) -> HashLife {
+        let mut life = HashLife::new(rule);
+        let size = frame.width().max(frame.height()).max(8);
+        let level = size.next_power_of_two().trailing_zeros() as u8;
+        life.root = life.build(frame, level, 0, 0);
+        life
+    }
+
+    /// the number of generations the plane has been advanced
+    pub fn generation(&self) -> u64 {
+        self.generation
+    }
+
+    /// the number of alive squares
+    pub fn population(&self) -> u64 {
+        self.nodes[self.root].population
+    }
+
+    /// the rule the plane advances with
+    pub fn rule(&self) -> &LifeLike {
+        &self.rule
+    }
+
+    /// the state at (x, y)
+    pub fn get(&self, x: i64, y: i64) -> State {
+        let (ox, oy) = self.origin;
+        let level = self.nodes[self.root].level;
+        let size = 1i64 << level;
+        if x < ox || y < oy || x >= ox + size || y >= oy + size {
+            return State::Dead;
+        }
+
+        let (mut node, mut x, mut y) = (self.root, x - ox, y - oy);
+        for level in (0..level).rev() {
+            let half = 1i64 << level;
+            let quadrant = (x >= half) as usize + 2 * (y >= half) as usize;
+            node = self.nodes[node].children[quadrant];
+            x %= half;
+            y %= half;
+        }
+        if node == ALIVE { State::Alive } else { State::Dead }
+    }
+
+    /// change the state at (x, y)
+    pub fn set(&mut self, x: i64, y: i64, state: State) {
+        loop {
+            let (ox, oy) = self.origin;
+            let size = 1i64 << self.nodes[self.root].level;
+            if x >= ox && y >= oy && x < ox + size && y < oy + size {
+                break;
+            }
+            self.expand();
+        }
+        let (ox, oy) = self.origin;
+        let leaf = if state == State::Alive { ALIVE } else { DEAD };
+        self.root = self.set_node(self.root, x - ox, y - oy, leaf);
+    }
+
+    /// The smallest rectangle containing every alive square, as the
+    /// coordinates of its top left corner and its width and height. None if
+    /// there are no alive squares
+    pub fn bounding_box(&self) -> Option<(i64, i64, u64, u64)> {
+        let mut bounds = None;
+        self.extend_bounds(self.root, self.origin, &mut bounds);
+        bounds.map(|(x0, y0, x1, y1)| {
+            (x0, y0, (x1 - x0 + 1) as u64, (y1 - y0 + 1) as u64)
+        })
+    }
+
+    /// The squares of a rectangle with its top left corner at (x, y) as a
+    /// frame with dead edges
+    pub fn to_frame(&self, x: i64, y: i64, width: usize, height: usize)
+        -> Frame<State> {
+        let mut frame = Frame::new_finite(width, height);
+        self.fill(self.root, self.origin, (x, y), &mut frame);
+        frame
+    }
+
+    /// The squares inside of the bounding box as a frame with dead edges
+    pub fn bounding_frame(&self) -> Frame<State> {
+        match self.bounding_box() {
+            Some((x, y, w, h)) => self.to_frame(x, y, w as usize, h as usize),
+            None => Frame::new_finite(0, 0),
+        }
+    }
+
+    /// Advance the plane 2^k generations at once, where k is at most MAX_STEP
+    pub fn step_pow2(&mut self, k: u8) {
+        assert!(k <= MAX_STEP, "can't step more than 2^{} generations at once",
+                MAX_STEP);
+        // the root must be big enough to step that far and have enough empty
+        // space around the pattern that it can't grow out of the result, which
+        // is the center of the root. Expanding once more after the pattern is
+        // in the center leaves room for it to grow by 2^k in every direction
+        while self.nodes[self.root].level < k + 2 || !self.is_padded() {
+            self.expand();
+        }
+        self.expand();
+
+        let level = self.nodes[self.root].level;
+        let quarter = 1i64 << (level - 2);
+        self.root = self.step(self.root, k);
+        self.origin = (self.origin.0 + quarter, self.origin.1 + quarter);
+        self.generation = self.generation.checked_add(1 << k)
+            .expect("the generation count overflowed");
+    }
+
+    /// Advance the plane any number of generations
+    pub fn advance(&mut self, generations: u64) {
+        for k in 0..MAX_STEP {
+            if generations & (1 << k) != 0 {
+                self.step_pow2(k);
+            }
+        }
+        // the rest is a multiple of the largest step
+        for _ in 0..generations >> MAX_STEP {
+            self.step_pow2(MAX_STEP);
+        }
+    }
+
+    /// Forget the memoized results to free memory
+    pub fn clear_cache(&mut self) {
+        self.results.clear();
+    }
+
+    /// The node made of four quadrants
+    fn join(&mut self, children: [NodeId; 4]) -> NodeId {
+        if let Some(&id) = self.index.get(&children) {
+            return id;
+        }
+        let node = Node {
+            level: self.nodes[children[0]].level + 1,
+            children,
+            population: children.iter()
+                .map(|&c| { self.nodes[c].population })
+                .sum(),
+        };
+        self.nodes.push(node);
+        self.index.insert(children, self.nodes.len() - 1);
+        self.nodes.len() - 1
+    }
+
+    /// The node of a level with no alive squares
+    fn empty_node(&mut self, level: u8) -> NodeId {
+        while self.empty.len() <= level as usize {
+            let e = *self.empty.last().unwrap();
+            let next = self.join([e; 4]);
+            self.empty.push(next);
+        }
+        self.empty[level as usize]
+    }
+
+    /// Build the node of a level covering the squares of the frame with its
+    /// top left corner at (x, y)
+    fn build(&mut self, frame: &Frame<State>, level: u8, x: usize, y: usize)
+        -> NodeId {
+        if x >= frame.width() || y >= frame.height() {
+            return self.empty_node(level);
+        }
+        if level == 0 {
+            return if *frame.get(x, y) == State::Alive { ALIVE } else { DEAD };
+        }
+        let half = 1 << (level - 1);
+        let children = [
+            self.build(frame, level - 1, x, y),
+            self.build(frame, level - 1, x + half, y),
+            self.build(frame, level - 1, x, y + half),
+            self.build(frame, level - 1, x + half, y + half),
+        ];
+        self.join(children)
+    }
+
+    /// Replace the square at (x, y) relative to a node with a leaf
+    fn set_node(&mut self, node: NodeId, x: i64, y: i64, leaf: NodeId)
+        -> NodeId {
+        let Node { level, mut children, .. } = self.nodes[node];
+        if level == 0 {
+            return leaf;
+        }
+        let half = 1i64 << (level - 1);
+        let quadrant = (x >= half) as usize + 2 * (y >= half) as usize;
+        children[quadrant] =
+            self.set_node(children[quadrant], x % half, y % half, leaf);
+        self.join(children)
+    }
+
+    /// Double the size of the root, keeping it centered on the same point
+    fn expand(&mut self) {
+        let Node { level, children: [nw, ne, sw, se], .. } =
+            self.nodes[self.root];
+        assert!(level < MAX_LEVEL, "the pattern grew past the edge of the plane");
+        let e = self.empty_node(level - 1);
+        let children = [
+            self.join([e, e, e, nw]),
+            self.join([e, e, ne, e]),
+            self.join([e, sw, e, e]),
+            self.join([se, e, e, e]),
+        ];
+        self.root = self.join(children);
+        let half = 1i64 << (level - 1);
+        self.origin = (self.origin.0 - half, self.origin.1 - half);
+    }
+
+    /// Whether every alive square of the root is in the node of the level
+    /// below centered in it
+    fn is_padded(&self) -> bool {
+        let [nw, ne, sw, se] = self.nodes[self.root].children;
+        let center = self.nodes[self.nodes[nw].children[3]].population
+            + self.nodes[self.nodes[ne].children[2]].population
+            + self.nodes[self.nodes[sw].children[1]].population
+            + self.nodes[self.nodes[se].children[0]].population;
+        center == self.population()
+    }
+
+    /// The center of a node of level at least 2 advanced 2^k generations,
+    /// where k is at most the level minus 2
+    fn step(&mut self, node: NodeId, k: u8) -> NodeId {
+        if let Some(&result) = self.results.get(&(node, k)) {
+            return result;
+        }
+        let Node { level, population, .. } = self.nodes[node];
+        let result = if population == 0 {
+            self.empty_node(level - 1)
+        } else if level == 2 {
+            self.step_leaves(node)
+        } else {
+            self.step_branch(node, k)
+        };
+        self.results.insert((node, k), result);
+        result
+    }
+
+    /// The center of a node of level 2 advanced one generation
+    fn step_leaves(&mut self, node: NodeId) -> NodeId {
+        // read the 4 by 4 squares of the node
+        let mut cells = [[false; 4]; 4];
+        for (q, &child) in self.nodes[node].children.iter().enumerate() {
+            for (c, &leaf) in self.nodes[child].children.iter().enumerate() {
+                let x = 2 * (q % 2) + c % 2;
+                let y = 2 * (q / 2) + c / 2;
+                cells[y][x] = leaf == ALIVE;
+            }
+        }
+
+        // the neighborhood of a Life-like rule has a radius of 1
+        let hood = self.rule.neighborhood();
+        let mut center = [DEAD; 4];
+        for (i, leaf) in center.iter_mut().enumerate() {
+            let (x, y) = (1 + i % 2, 1 + i / 2);
+            let n = hood.offsets()
+                .filter(|&(dx, dy)| {
+                    cells[(y as isize + dy) as usize][(x as isize + dx) as usize]
+                })
+                .count();
+            let curr = if cells[y][x] { State::Alive } else { State::Dead };
+            if self.rule.next_state(curr, n) == State::Alive {
+                *leaf = ALIVE;
+            }
+        }
+        self.join(center)
+    }
+
+    /// The center of a node of level at least 3 advanced 2^k generations
+    fn step_branch(&mut self, node: NodeId, k: u8) -> NodeId {
+        let level = self.nodes[node].level;
+        let [nw, ne, sw, se] = self.nodes[node].children;
+        // the quadrants of the quadrants laid out as
+        // _ b e _
+        // c d g h
+        // i j n o
+        // _ m p _
+        let [_, b, c, d] = self.nodes[nw].children;
+        let [e, _, g, h] = self.nodes[ne].children;
+        let [i, j, _, m] = self.nodes[sw].children;
+        let [n, o, p, _] = self.nodes[se].children;
+
+        // the nine overlapping nodes of the level below that tile the center
+        let parts = [
+            nw, self.join([b, e, d, g]), ne,
+            self.join([c, d, i, j]), self.join([d, g, j, n]), self.join([g, h, n, o]),
+            sw, self.join([j, n, m, p]), se,
+        ];
+
+        // advance each of them half of the way if k is as big as possible,
+        // otherwise only take their centers and leave the advancing for later
+        let full = k + 2 == level;
+        let mut centers = [DEAD; 9];
+        for (center, &part) in centers.iter_mut().zip(parts.iter()) {
+            *center = if full { self.step(part, k - 1) } else { self.center(part) };
+        }
+
+        let quadrants = [
+            [centers[0], centers[1], centers[3], centers[4]],
+            [centers[1], centers[2], centers[4], centers[5]],
+            [centers[3], centers[4], centers[6], centers[7]],
+            [centers[4], centers[5], centers[7], centers[8]],
+        ];
+        let mut result = [DEAD; 4];
+        for (r, &quadrant) in result.iter_mut().zip(quadrants.iter()) {
+            let joined = self.join(quadrant);
+            *r = self.step(joined, if full { k - 1 } else { k });
+        }
+        self.join(result)
+    }
+
+    /// The node of the level below made of the middle of a node
+    fn center(&mut self, node: NodeId) -> NodeId {
+        let [nw, ne, sw, se] = self.nodes[node].children;
+        let children = [
+            self.nodes[nw].children[3],
+            self.nodes[ne].children[2],
+            self.nodes[sw].children[1],
+            self.nodes[se].children[0],
+        ];
+        self.join(children)
+    }
+
+    /// Grow bounds to include every alive square of a node whose top left
+    /// corner is at origin
+    fn extend_bounds(&self, node: NodeId, origin: (i64, i64),
+                     bounds: &mut Option<(i64, i64, i64, i64)>) {
+        let Node { level, children, population } = self.nodes[node];
+        if population == 0 {
+            return;
+        }
+        let (x, y) = origin;
+        if level == 0 {
+            *bounds = Some(match *bounds {
+                None => (x, y, x, y),
+                Some((x0, y0, x1, y1)) =>
+                    (x0.min(x), y0.min(y), x1.max(x), y1.max(y)),
+            });
+            return;
+        }
+        let half = 1i64 << (level - 1);
+        for (q, &child) in children.iter().enumerate() {
+            let corner = (x + half * (q % 2) as i64, y + half * (q / 2) as i64);
+            self.extend_bounds(child, corner, bounds);
+        }
+    }
+
+    /// Copy the alive squares of a node whose top left corner is at origin
+    /// into a frame whose top left corner is at corner
+    fn fill(&self, node: NodeId, origin: (i64, i64), corner: (i64, i64),
+            frame: &mut Frame<State>) {
+        let Node { level, children, population } = self.nodes[node];
+        let (x, y) = origin;
+        let size = 1i64 << level;
+        let (fx, fy) = corner;
+        let (fw, fh) = (frame.width() as i64, frame.height() as i64);
+        if population == 0 || x >= fx + fw || y >= fy + fh
+            || x + size <= fx || y + size <= fy {
+            return;
+        }
+        if level == 0 {
+            *frame.get_mut((x - fx) as usize, (y - fy) as usize) = State::Alive;
+            return;
+        }
+        let half = 1i64 << (level - 1);
+        for (q, &child) in children.iter().enumerate() {
+            let origin = (x + half * (q % 2) as i64, y + half * (q / 2) as i64);
+            self.fill(child, origin, corner, frame);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::{Boundary, Frame};
+    use super::super::frame::tests::scattered;
+    use super::super::game_of_life::{LifeLike, State};
+    use super::super::game_of_life::State::Alive;
+    use super::{HashLife, MAX_STEP};
+
+    /// A glider heading south east with its top left corner at (x, y)
+    fn glider(frame: &mut Frame<State>, x: usize, y: usize) {
+        *frame.get_mut(x + 1, y) = Alive;
+        *frame.get_mut(x + 2, y + 1) = Alive;
+        *frame.get_mut(x, y + 2) = Alive;
+        *frame.get_mut(x + 1, y + 2) = Alive;
+        *frame.get_mut(x + 2, y + 2) = Alive;
+    }
+
+    #[test]
+    fn conversion() {
+        let mut frame = Frame::<State>::new_finite(13, 9);
+        glider(&mut frame, 4, 3);
+        let life = HashLife::from_frame(&frame, LifeLike::conway());
+        assert_eq!(life.population(), 5);
+        assert_eq!(life.get(5, 3), Alive);
+        assert_eq!(life.get(-100, 3), State::Dead);
+        assert_eq!(life.bounding_box(), Some((4, 3, 3, 3)));
+        assert_eq!(life.to_frame(0, 0, 13, 9), frame);
+    }
+
+    #[test]
+    fn set() {
+        let mut life = HashLife::new(LifeLike::conway());
+        life.set(-40, 70, Alive);
+        life.set(3, 2, Alive);
+        assert_eq!(life.get(-40, 70), Alive);
+        assert_eq!(life.population(), 2);
+        life.set(3, 2, State::Dead);
+        assert_eq!(life.bounding_box(), Some((-40, 70, 1, 1)));
+    }
+
+    #[test]
+    fn matches_frame() {
+        // a small soup in the middle of a frame that is big enough that
+        // nothing reaches the edges
+        let rules = ["B3/S23", "B36/S23", "B2/S", "B3678/S34678", "B2/S34H"];
+        for rulestring in rules.iter() {
+            let rule = rulestring.parse::<LifeLike>().unwrap();
+            let mut frame = Frame::<State>::new_finite(64, 64);
+            let soup = scattered(8, 8, Boundary::Toroidal);
+            for (x, y, &state) in soup.enumerate_squares() {
+                *frame.get_mut(x + 28, y + 28) = state;
+            }
+
+            let mut life = HashLife::from_frame(&frame, rule.clone());
+            for generations in [1, 2, 3, 8, 2].iter() {
+                life.advance(*generations);
+                for _ in 0..*generations {
+                    frame = frame.advance(&rule);
+                }
+                assert_eq!(life.to_frame(0, 0, 64, 64), frame);
+            }
+            assert_eq!(life.generation(), 16);
+        }
+    }
+
+    #[test]
+    fn far_future() {
+        // a glider moves one square diagonally every four generations
+        let mut frame = Frame::<State>::new_finite(3, 3);
+        glider(&mut frame, 0, 0);
+        let mut life = HashLife::from_frame(&frame, LifeLike::conway());
+        life.step_pow2(20);
+
+        let moved = 1 << 18;
+        assert_eq!(life.population(), 5);
+        assert_eq!(life.bounding_box(), Some((moved, moved, 3, 3)));
+        assert_eq!(life.to_frame(moved, moved, 3, 3), frame);
+    }
+    #[test]
+    fn largest_steps() {
+        // a block never changes, so it can be advanced as far as a u64 counts
+        let mut frame = Frame::<State>::new_finite(2, 2);
+        for &(x, y) in [(0, 0), (1, 0), (0, 1), (1, 1)].iter() {
+            *frame.get_mut(x, y) = State::Alive;
+        }
+        let mut life = HashLife::from_frame(&frame, LifeLike::conway());
+        life.advance(u64::MAX);
+        assert_eq!(life.generation(), u64::MAX);
+        assert_eq!(life.bounding_box(), Some((0, 0, 2, 2)));
+    }
+
+    #[test]
+    #[should_panic(expected = "edge of the plane")]
+    fn leaves_the_plane() {
+        // a glider would move 2^62 squares
+        let mut frame = Frame::<State>::new_finite(3, 3);
+        glider(&mut frame, 0, 0);
+        let mut life = HashLife::from_frame(&frame, LifeLike::conway());
+        life.advance(u64::MAX);
+    }
+
+    #[test]
+    #[should_panic(expected = "at once")]
+    fn step_too_large() {
+        HashLife::new(LifeLike::conway()).step_pow2(MAX_STEP + 1);
+    }
+}
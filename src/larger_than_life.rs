@@ -0,0 +1,369 @@
+use super::{Frame, Neighborhood, Rule, Square, ParseRuleError};
+use super::game_of_life::State;
+use super::rule::{split_field, find_named};
+
+use std::fmt;
+use std::ops::RangeInclusive;
+use std::str::FromStr;
+
+/// Well known Larger than Life rules and their rulestrings
+pub const CATALOG: &[(&str, &str)] = &[
+    ("Bosco's Rule", "R5,C0,M1,S34..58,B34..45,NM"),
+    ("Majority", "R4,C0,M1,S41..81,B41..81,NM"),
+    ("Waffle", "R7,C0,M1,S100..200,B75..170,NM"),
+];
+
+/// A two state rule over a large neighborhood where a square is born or
+/// survives if the number of alive squares around it is within a range
+#[derive(Clone, Debug, PartialEq)]
+pub struct LargerThanLife {
+    name: String,
+    neighborhood: Neighborhood,
+    /// whether the square itself is counted along with its neighbors
+    middle: bool,
+    birth: RangeInclusive<usize>,
+    survival: RangeInclusive<usize>,
+}
+
+impl LargerThanLife {
+    /// Create a rule from its neighborhood, which must be a Moore, von Neumann
+    /// or circular neighborhood, and the ranges that cause birth and survival.
+    /// Panics for any other neighborhood
+    pub fn new(neighborhood: Neighborhood, middle: bool,
+               birth: RangeInclusive<usize>, survival: RangeInclusive<usize>)
+        -> LargerThanLife {
+        match neighborhood {
+            Neighborhood::Moore(_) | Neighborhood::VonNeumann(_)
+                | Neighborhood::Circular(_) => {},
+            _ => panic!("{:?} isn't a Moore, von Neumann or circular \
+                         neighborhood", neighborhood),
+        }
+        let mut rule = LargerThanLife {
+            name: String::new(),
+            neighborhood,
+            middle,
+            birth,
+            survival,
+        };
+        rule.name = rule.rulestring();
+        rule
+    }
+
+    /// The rule in CATALOG with a name like "Bosco's Rule"
+    pub fn named(name: &str) -> Option<LargerThanLife> {
+        find_named(CATALOG, name).map(|(n, rule)| {
+            LargerThanLife { name: n.to_string(), ..rule }
+        })
+    }
+
+    /// The rule in Golly's notation, e.g. "R5,C0,M1,S34..58,B34..45,NM"
+    pub fn rulestring(&self) -> String {
+        let shape = match self.neighborhood {
+            Neighborhood::VonNeumann(_) => 'N',
+            Neighborhood::Circular(_) => 'C',
+            _ => 'M',
+        };
+        format!("R{},C0,M{},S{}..{},B{}..{},N{}",
+                self.neighborhood.radius(), self.middle as u8,
+                self.survival.start(), self.survival.end(),
+                self.birth.start(), self.birth.end(), shape)
+    }
+
+    /// The next state of a square that is currently in state curr and has n
+    /// alive squares in its neighborhood, including itself if the middle is
+    /// counted
+    pub fn next_state(&self, curr: State, n: usize) -> State {
+        let alive = match curr {
+            State::Alive => self.survival.contains(&n),
+            State::Dead => self.birth.contains(&n),
+        };
+        if alive { State::Alive } else { State::Dead }
+    }
+
+    /// Return the next frame of the simulation. This computes the same frame as
+    /// advancing with the rule, but it counts the squares in each neighborhood
+    /// with prefix sums so the cost doesn't grow with the area of the
+    /// neighborhood
+    pub fn next_frame(&self, frame: &Frame<State>) -> Frame<State> {
+        if frame.width() == 0 || frame.height() == 0 {
+            // there are no squares to pad the frame with
+            return frame.clone();
+        }
+        let r = self.neighborhood.radius();
+        let counts = PrefixSums::new(frame, r);
+        let widths = self.half_widths();
+
+        let mut next = frame.clone();
+        for (x, y, &curr) in frame.enumerate_squares() {
+            // the square is at (x + r, y + r) in the padded sums
+            let (px, py) = (x + r, y + r);
+            let mut n = match self.neighborhood {
+                Neighborhood::Moore(_) =>
+                    counts.rectangle(px - r, py - r, px + r, py + r),
+                _ => widths.iter().enumerate().map(|(row, &half)| {
+                    counts.rectangle(px - half, py - r + row,
+                                     px + half, py - r + row)
+                }).sum(),
+            };
+            if !self.middle && curr == State::Alive {
+                n -= 1;
+            }
+            *next.get_mut(x, y) = frame.fixed(x, y)
+                .unwrap_or_else(|| { self.next_state(curr, n) });
+        }
+        next
+    }
+
+    /// Half of the width of the neighborhood on each of its rows from top to
+    /// bottom
+    fn half_widths(&self) -> Vec<usize> {
+        let r = self.neighborhood.radius();
+        // on a circle the rows get wider towards the middle, so the width of
+        // each row is found by walking inwards from the one before it
+        let mut dx = 0;
+        let mut widths = (0..r + 1).rev().map(|dy| {
+            match self.neighborhood {
+                Neighborhood::Moore(_) => r,
+                Neighborhood::VonNeumann(_) => r - dy,
+                _ => {
+                    while dx < r && (dx + 1) * (dx + 1) + dy * dy <= r * r {
+                        dx += 1;
+                    }
+                    dx
+                },
+            }
+        }).collect::<Vec<_>>();
+        let bottom = widths.iter().rev().skip(1).cloned().collect::<Vec<_>>();
+        widths.extend(bottom);
+        widths
+    }
+}
+
+/// Summed area table over a frame padded on every side according to its
+/// boundary condition
+struct PrefixSums {
+    /// the number of alive squares above and to the left of each point
+    sums: Vec<usize>,
+    width: usize,
+}
+
+impl PrefixSums {
+    fn new(frame: &Frame<State>, padding: usize) -> PrefixSums {
+        let p = padding as isize;
+        let width = frame.width() + 2 * padding + 1;
+        let height = frame.height() + 2 * padding + 1;
+        let mut sums = vec![0; width * height];
+        for y in 1..height {
+            let mut row = 0;
+            for x in 1..width {
+                let cell = frame.at(x as isize - 1 - p, y as isize - 1 - p);
+                row += (*cell == State::Alive) as usize;
+                sums[y * width + x] = sums[(y - 1) * width + x] + row;
+            }
+        }
+        PrefixSums { sums, width }
+    }
+
+    /// The number of alive squares within a rectangle of the padded frame,
+    /// inclusive of both corners
+    fn rectangle(&self, x0: usize, y0: usize, x1: usize, y1: usize) -> usize {
+        let at = |x: usize, y: usize| { self.sums[y * self.width + x] };
+        at(x1 + 1, y1 + 1) + at(x0, y0) - at(x0, y1 + 1) - at(x1 + 1, y0)
+    }
+}
+
+impl FromStr for LargerThanLife {
+    type Err = ParseRuleError;
+
+    /// Parse a rulestring in Golly's notation, or the name of a rule in the
+    /// catalog
+    fn from_str(rulestring: &str) -> Result<LargerThanLife, ParseRuleError> {
+        if let Some(rule) = LargerThanLife::named(rulestring) {
+            return Ok(rule);
+        }
+
+        let error = |reason: &str| { ParseRuleError::new(rulestring, reason) };
+        let number = |s: &str| {
+            s.trim().parse::<usize>()
+                .map_err(|_| { error(&format!("{:?} is not a number", s)) })
+        };
+        let range = |s: &str| {
+            let mut bounds = s.split("..");
+            let start = number(bounds.next().unwrap_or(""))?;
+            let end = bounds.next().map_or(Ok(start), &number)?;
+            match bounds.next() {
+                None => Ok(start..=end),
+                Some(_) => Err(error("ranges are written like 34..58")),
+            }
+        };
+
+        let (mut radius, mut middle) = (None, false);
+        let (mut birth, mut survival, mut shape) = (None, None, 'M');
+        for field in rulestring.split(',').map(str::trim) {
+            let (key, value) = split_field(field);
+            match key {
+                Some('R') => radius = Some(number(value)?),
+                Some('C') => match number(value)? {
+                    0 | 2 => {},
+                    _ => return Err(error("only two state rules are supported")),
+                },
+                Some('M') => middle = number(value)? == 1,
+                Some('S') => survival = Some(range(value)?),
+                Some('B') => birth = Some(range(value)?),
+                Some('N') => shape = value.chars().next()
+                    .map(|c| { c.to_ascii_uppercase() })
+                    .unwrap_or(' '),
+                _ => return Err(error(&format!("unknown field {:?}", field))),
+            }
+        }
+
+        let radius = radius.filter(|&r| { r >= 1 })
+            .ok_or_else(|| { error("expected a radius of at least 1") })?;
+        let neighborhood = match shape {
+            'M' => Neighborhood::Moore(radius),
+            'N' => Neighborhood::VonNeumann(radius),
+            'C' => Neighborhood::Circular(radius),
+            _ => return Err(error("the neighborhood must be NM, NN or NC")),
+        };
+        let birth = birth.ok_or_else(|| { error("expected a birth range") })?;
+        let survival = survival
+            .ok_or_else(|| { error("expected a survival range") })?;
+        Ok(LargerThanLife::new(neighborhood, middle, birth, survival))
+    }
+}
+
+impl fmt::Display for LargerThanLife {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.rulestring())
+    }
+}
+
+impl Rule for LargerThanLife {
+    type State = State;
+
+    fn name(&self) -> &str { &self.name }
+    fn neighborhood(&self) -> Neighborhood { self.neighborhood.clone() }
+    fn state_count(&self) -> Option<usize> { Some(2) }
+
+    fn step(&self, curr: Square<State>) -> State {
+        let this = *curr.get(0, 0);
+        let mut n = curr.count_neighbors(&self.neighborhood,
+                                         |e| { *e == State::Alive });
+        if self.middle && this == State::Alive {
+            n += 1;
+        }
+        self.next_state(this, n)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::{Boundary, Mask, Neighborhood, Rule};
+    use super::super::frame::tests::scattered;
+    use super::super::game_of_life::{rule, State};
+    use super::super::game_of_life::State::Alive;
+    use super::{LargerThanLife, CATALOG};
+
+    #[test]
+    fn parse() {
+        let bosco = "R5,C0,M1,S34..58,B34..45,NM".parse::<LargerThanLife>()
+            .unwrap();
+        assert_eq!(bosco, LargerThanLife::new(Neighborhood::Moore(5), true,
+                                              34..=45, 34..=58));
+        assert_eq!(bosco.rulestring(), "R5,C0,M1,S34..58,B34..45,NM");
+        let circle = "r3,c2,m0,s5..9,b7,nc".parse::<LargerThanLife>().unwrap();
+        assert_eq!(circle.neighborhood(), Neighborhood::Circular(3));
+        assert_eq!(circle.rulestring(), "R3,C0,M0,S5..9,B7..7,NC");
+        assert!("R5,C3,M1,S34..58,B34..45,NM".parse::<LargerThanLife>().is_err());
+        assert!("R5,M1,S34..58,NM".parse::<LargerThanLife>().is_err());
+        assert!("R0,M1,S1..2,B1..2,NM".parse::<LargerThanLife>().is_err());
+        assert!("R2,M1,S1..2,B1..2,NX".parse::<LargerThanLife>().is_err());
+        assert!("R5,é,S1..2,B1..2,NM".parse::<LargerThanLife>().is_err());
+        assert!("R5,C0,Mé,S1..2,B1..2,NM".parse::<LargerThanLife>().is_err());
+    }
+
+    #[test]
+    fn catalog() {
+        for &(name, rulestring) in CATALOG {
+            let rule = LargerThanLife::named(name).unwrap();
+            assert_eq!(rule.name(), name);
+            assert_eq!(rule.rulestring(), rulestring);
+        }
+    }
+
+    #[test]
+    fn radius_one_is_life() {
+        let life = "R1,C0,M0,S2..3,B3..3,NM".parse::<LargerThanLife>().unwrap();
+        let frame = scattered(8, 8, Boundary::Toroidal);
+        assert_eq!(frame.advance(&life), frame.next_frame(rule));
+        assert_eq!(life.next_frame(&frame), frame.next_frame(rule));
+    }
+
+    #[test]
+    #[should_panic(expected = "isn't a Moore")]
+    fn other_neighborhoods() {
+        LargerThanLife::new(Neighborhood::Hexagonal(2), true, 3..=4, 3..=5);
+    }
+
+    #[test]
+    fn matches_brute_force() {
+        // count every square within the shape of the neighborhood one by one
+        let inside = |shape: char, r: isize, dx: isize, dy: isize| {
+            match shape {
+                'N' => dx.abs() + dy.abs() <= r,
+                'C' => dx * dx + dy * dy <= r * r,
+                _ => true,
+            }
+        };
+        let frame = scattered(13, 9, Boundary::Toroidal);
+        for shape in ['M', 'N', 'C'].iter() {
+            for r in 1..5 {
+                let rulestring = format!("R{},C0,M1,S{}..{},B{}..{},N{}", r,
+                                         r * 2, r * 4, r * 2, r * 3, shape);
+                let rule = rulestring.parse::<LargerThanLife>().unwrap();
+                let next = rule.next_frame(&frame);
+                for (x, y, &curr) in frame.enumerate_squares() {
+                    let (x, y, r) = (x as isize, y as isize, r as isize);
+                    let n = (-r..r + 1).flat_map(|dy| {
+                        (-r..r + 1).map(move |dx| { (dx, dy) })
+                    }).filter(|&(dx, dy)| {
+                        inside(*shape, r, dx, dy)
+                            && *frame.at(x + dx, y + dy) == Alive
+                    }).count();
+                    assert_eq!(*next.get(x as usize, y as usize),
+                               rule.next_state(curr, n), "{}", rulestring);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn fast_path_matches_rule() {
+        let rules = [
+            "R3,C0,M1,S8..20,B9..15,NM",
+            "R2,C0,M0,S3..6,B4..5,NN",
+            "R3,C0,M1,S6..14,B7..10,NC",
+        ];
+        let boundaries = [
+            Boundary::Toroidal,
+            Boundary::Constant(State::Dead),
+            Boundary::Reflecting,
+            Boundary::KleinBottle,
+            Boundary::Helical(2),
+        ];
+        for rulestring in rules.iter() {
+            let rule = rulestring.parse::<LargerThanLife>().unwrap();
+            for boundary in boundaries.iter() {
+                let mut frame = scattered(11, 7, boundary.clone());
+                assert_eq!(rule.next_frame(&frame), frame.advance(&rule));
+                frame.mask_rect(0, 3, 11, 1, Mask::Wall);
+                frame.set_mask(5, 5, Mask::Emitter(Alive));
+                assert_eq!(rule.next_frame(&frame), frame.advance(&rule));
+                for &(w, h) in [(0, 0), (0, 5), (5, 0)].iter() {
+                    let empty = scattered(w, h, boundary.clone());
+                    assert_eq!(rule.next_frame(&empty), empty);
+                }
+            }
+        }
+    }
+}
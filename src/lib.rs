@@ -3,10 +3,69 @@ extern crate rand;
 /// Contains functions that deal with handling individual frames of a simulation
 mod frame;
 
+/// Contains frames, neighborhoods and rules in three dimensions
+mod frame3;
+
+/// Contains the shapes of neighborhoods that squares can look at
+mod neighborhood;
+
+/// Contains the trait shared by all of the rules
+mod rule;
+
+/// Contains what rules can see besides the surroundings of a square
+mod context;
+
+/// Contains the orders that squares can be updated in
+mod update;
+
+/// Contains a double buffered runner for simulations
+mod simulation;
+
 // Expose all of this at the root
 pub use frame::*;
+pub use frame3::*;
+pub use neighborhood::*;
+pub use rule::*;
+pub use context::*;
+pub use simulation::*;
+pub use update::*;
 
 /// Public functions that define Conway's Game of Life
 pub mod game_of_life;
 pub mod two_color_life;
 pub mod rainbow_life;
+
+/// Life-like rules where dying squares take several generations to die
+pub mod generations;
+
+/// Two state rules over large neighborhoods where births and survivals happen
+/// for ranges of counts
+pub mod larger_than_life;
+
+/// Two state rules on a cubic lattice in Carter Bays' notation
+pub mod life3d;
+
+/// One dimensional rules whose histories are drawn as spacetime diagrams
+pub mod one_dimensional;
+
+/// Simulations on arbitrary graphs, where cells are nodes and neighbors are
+/// adjacency lists
+pub mod graph;
+
+/// A bit packed frame for quickly advancing Life-like rules
+pub mod bit_grid;
+
+/// The HashLife algorithm for advancing Life-like rules far into the future
+pub mod hashlife;
+
+/// An unbounded grid that only stores the parts that aren't empty
+pub mod sparse;
+
+/// Rules over finitely many states compiled into lookup tables
+pub mod compiled;
+
+/// Rules made of several rules that each apply in their own zone of a frame
+pub mod zones;
+
+/// Rules that change over time by following a schedule of other rules
+pub mod schedule;
@@ -0,0 +1,195 @@
+use super::{Neighborhood3, ParseRuleError, Rule3, Square3, MOORE3,
+            VON_NEUMANN3};
+use super::game_of_life::State;
+
+use std::fmt;
+use std::ops::RangeInclusive;
+use std::str::FromStr;
+
+/// A two state rule on a cubic lattice in Carter Bays' notation, where an
+/// alive cube survives if the number of alive cubes around it is within the
+/// environment range and a dead cube is born if it is within the fertility
+/// range
+#[derive(Clone, Debug, PartialEq)]
+pub struct Life3 {
+    name: String,
+    neighborhood: Neighborhood3,
+    environment: RangeInclusive<usize>,
+    fertility: RangeInclusive<usize>,
+}
+
+impl Life3 {
+    /// Create a rule over the 26 neighbors of a cube
+    pub fn new(environment: RangeInclusive<usize>,
+               fertility: RangeInclusive<usize>) -> Life3 {
+        Life3::with_neighborhood(MOORE3, environment, fertility)
+    }
+
+    /// Create a rule over a neighborhood, which must have a radius of 1
+    pub fn with_neighborhood(neighborhood: Neighborhood3,
+                             environment: RangeInclusive<usize>,
+                             fertility: RangeInclusive<usize>) -> Life3 {
+        let mut rule = Life3 {
+            name: String::new(),
+            neighborhood,
+            environment,
+            fertility,
+        };
+        rule.name = rule.rulestring();
+        rule
+    }
+
+    /// The rule in Bays' notation, e.g. "5766" for survival with 5 to 7 alive
+    /// neighbors and birth with 6. The bounds are separated by slashes if any
+    /// of them has more than one digit, and the rulestring ends in V if the
+    /// neighbors are the 6 cubes that share a face
+    pub fn rulestring(&self) -> String {
+        let bounds = [self.environment.start(), self.environment.end(),
+                      self.fertility.start(), self.fertility.end()];
+        let bounds = bounds.iter().map(|n| { n.to_string() })
+            .collect::<Vec<_>>();
+        let separator = if bounds.iter().all(|n| { n.len() == 1 }) {
+            ""
+        } else {
+            "/"
+        };
+        let suffix = if self.neighborhood == VON_NEUMANN3 { "V" } else { "" };
+        format!("{}{}", bounds.join(separator), suffix)
+    }
+
+    /// The next state of a cube that is currently in state curr and has n
+    /// alive neighbors
+    pub fn next_state(&self, curr: State, n: usize) -> State {
+        let alive = match curr {
+            State::Alive => self.environment.contains(&n),
+            State::Dead => self.fertility.contains(&n),
+        };
+        if alive { State::Alive } else { State::Dead }
+    }
+}
+
+impl FromStr for Life3 {
+    type Err = ParseRuleError;
+
+    /// Parse a rulestring in Bays' notation like "4555" or "4/5/5/5"
+    fn from_str(rulestring: &str) -> Result<Life3, ParseRuleError> {
+        let error = |reason: String| {
+            ParseRuleError::new(rulestring, reason)
+        };
+        let trimmed = rulestring.trim();
+        let (neighborhood, bounds) = match trimmed.strip_suffix(['V', 'v']) {
+            Some(bounds) => (VON_NEUMANN3, bounds),
+            None => (MOORE3, trimmed),
+        };
+        let bounds = if bounds.contains('/') {
+            bounds.split('/').map(|s| { s.trim().parse::<usize>().ok() })
+                .collect::<Vec<_>>()
+        } else {
+            bounds.chars().map(|c| { c.to_digit(10).map(|n| { n as usize }) })
+                .collect()
+        };
+        let bounds = bounds.into_iter().collect::<Option<Vec<_>>>()
+            .filter(|b| { b.len() == 4 })
+            .ok_or_else(|| {
+                error("expected four bounds like 4555 or 4/5/5/5".to_string())
+            })?;
+
+        let max = neighborhood.len();
+        if let Some(n) = bounds.iter().find(|&&n| { n > max }) {
+            return Err(error(format!("{} is not a neighbor count between 0 \
+                                      and {}", n, max)));
+        }
+        if bounds[0] > bounds[1] || bounds[2] > bounds[3] {
+            return Err(error("the lower bounds can't be above the upper \
+                              bounds".to_string()));
+        }
+        Ok(Life3::with_neighborhood(neighborhood, bounds[0]..=bounds[1],
+                                    bounds[2]..=bounds[3]))
+    }
+}
+
+impl fmt::Display for Life3 {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.rulestring())
+    }
+}
+
+impl Rule3 for Life3 {
+    type State = State;
+
+    fn name(&self) -> &str { &self.name }
+    fn neighborhood(&self) -> Neighborhood3 { self.neighborhood.clone() }
+
+    fn step(&self, curr: Square3<State>) -> State {
+        let n = curr.count_neighbors(&self.neighborhood,
+                                     |e| { *e == State::Alive });
+        self.next_state(*curr.get(0, 0, 0), n)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::{Frame3, Rule3, VON_NEUMANN3};
+    use super::super::game_of_life::State;
+    use super::super::game_of_life::State::Alive;
+    use super::Life3;
+
+    /// A frame with a 2 by 2 by 2 block of alive cubes in the middle
+    fn block() -> Frame3<State> {
+        let mut frame = Frame3::new(6, 6, 6);
+        for z in 2..4 {
+            for y in 2..4 {
+                for x in 2..4 {
+                    *frame.get_mut(x, y, z) = Alive;
+                }
+            }
+        }
+        frame
+    }
+
+    #[test]
+    fn parse() {
+        let rule = "4555".parse::<Life3>().unwrap();
+        assert_eq!(rule, Life3::new(4..=5, 5..=5));
+        assert_eq!(rule.name(), "4555");
+        assert_eq!("5/7/6/6".parse::<Life3>().unwrap().rulestring(), "5766");
+        let rule = "10/21/10/21".parse::<Life3>().unwrap();
+        assert_eq!(rule, Life3::new(10..=21, 10..=21));
+        assert_eq!(rule.rulestring(), "10/21/10/21");
+        let rule = "1311v".parse::<Life3>().unwrap();
+        assert_eq!(rule.neighborhood(), VON_NEUMANN3);
+        assert_eq!(rule.rulestring(), "1311V");
+        assert!("455".parse::<Life3>().is_err());
+        assert!("5/4/5/5".parse::<Life3>().is_err());
+        assert!("4/5/5/27".parse::<Life3>().is_err());
+        assert!("1177V".parse::<Life3>().is_err());
+        assert!("45x5".parse::<Life3>().is_err());
+    }
+
+    #[test]
+    fn block_is_still_in_5766() {
+        // every cube of the block has 7 alive neighbors and no dead cube has
+        // more than 4
+        let frame = block();
+        let rule = "5766".parse::<Life3>().unwrap();
+        assert_eq!(frame.advance(&rule), frame);
+        let rule = "4555".parse::<Life3>().unwrap();
+        assert_eq!(frame.advance(&rule), Frame3::new(6, 6, 6));
+    }
+
+    #[test]
+    fn faces() {
+        // with only the 6 cubes that share a face as neighbors, the cubes
+        // beside each face of the block have one alive neighbor and the cubes
+        // beside its edges have none
+        let frame = block();
+        let rule = "0011V".parse::<Life3>().unwrap();
+        let next = frame.advance(&rule);
+        assert_eq!(*next.get(1, 2, 2), Alive);
+        assert_eq!(*next.get(1, 1, 2), State::Dead);
+        assert_eq!(next.enumerate_cubes()
+                       .filter(|&(_, _, _, &e)| { e == Alive })
+                       .count(),
+                   24);
+    }
+}
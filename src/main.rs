@@ -8,8 +8,14 @@ use std::path::Path;
 use std::ops::Deref;
 use std::ops::DerefMut;
 
-use simulation::rainbow_life;
-use simulation::rainbow_life::State;
+use simulation::{Context, FiniteState, Frame, Frame3, Neighborhood, Rule,
+                 Rule3, Simulation};
+use simulation::game_of_life::{self, LifeLike};
+use simulation::graph::Graph;
+use simulation::life3d::Life3;
+use simulation::one_dimensional::{self, Elementary, Rule1, Totalistic};
+use simulation::rainbow_life::{HexRainbowLife, RainbowLife, State};
+use simulation::schedule::Schedule;
 
 use rand::Rng;
 use rand::Rand;
@@ -22,36 +28,345 @@ fn main() {
     let side = (imgdim / scale) as usize;
     let max_iters = 1000;
 
+    // a run can be repeated by passing the seed that it prints
+    let seed = std::env::args().nth(1)
+        .map(|s| { s.parse().expect("the seed should be a number") })
+        .unwrap_or_else(|| { Context::random().seed() });
+    println!("seed {}", seed);
+    let mut rng = Context::new(seed).rng(0, 0);
+
+    // a schedule of life-like rules can be passed after the seed, like
+    // "B3/S23; B36/S23" to alternate between them, "hex" for multicolored
+    // life on a hexagonal lattice, "3d" followed by a rulestring like 5766
+    // for life in three dimensions or "1d" followed by an elementary rule
+    // like 30 or a totalistic rule like K3,R1,C777 for a spacetime diagram,
+    // "graph" followed by a life-like rule to compare how many squares stay
+    // alive on a lattice and on random networks, otherwise the rule is
+    // multicolored life
+    let arg = std::env::args().nth(2);
+    if arg.as_ref().is_some_and(|s| { s == "graph" }) {
+        let rulestring = std::env::args().nth(3)
+            .unwrap_or_else(|| { "B3/S23".to_string() });
+        let rule = match rulestring.parse::<LifeLike>() {
+            Ok(rule) => rule,
+            Err(e) => {
+                println!("{}", e);
+                return;
+            },
+        };
+        let mut frame = Frame::new(side, side);
+        random_life_frame(&mut frame, &mut rng);
+        let lattice = Graph::from_frame(&frame, &rule.neighborhood());
+        let degree = rule.neighborhood().len();
+        let n = lattice.len();
+        let graphs = vec![
+            ("lattice", lattice.clone()),
+            ("small world", with_states(
+                Graph::small_world(n, degree, 0.1, &mut rng), &lattice)),
+            ("random regular", with_states(
+                Graph::random_regular(n, degree, &mut rng), &lattice)),
+        ];
+        compare_graphs(&rule, graphs, max_iters / 10);
+        return;
+    }
+    if arg.as_ref().is_some_and(|s| { s == "1d" }) {
+        let rulestring = std::env::args().nth(3)
+            .unwrap_or_else(|| { "30".to_string() });
+        let rule: Box<dyn Rule1> = match rulestring.parse::<Elementary>() {
+            Ok(rule) => Box::new(rule),
+            Err(_) => match rulestring.parse::<Totalistic>() {
+                Ok(rule) => Box::new(rule),
+                Err(e) => {
+                    println!("{}", e);
+                    return;
+                },
+            },
+        };
+        println!("running {}", rule.name());
+        let row = one_dimensional::single(side, 1);
+        let diagram = one_dimensional::spacetime(&*rule, &row, side,
+                                                 Default::default());
+        std::fs::create_dir_all("files").unwrap();
+        save(&diagram, "files/spacetime.png", imgdim, scale, Shape::Sharp);
+        return;
+    }
+    if arg.as_ref().is_some_and(|s| { s == "3d" }) {
+        let rulestring = std::env::args().nth(3)
+            .unwrap_or_else(|| { "5766".to_string() });
+        let rule = match rulestring.parse::<Life3>() {
+            Ok(rule) => rule,
+            Err(e) => {
+                println!("{}", e);
+                return;
+            },
+        };
+        let mut frame = Frame3::new(32, 32, 32);
+        random_life_frame3(&mut frame, &mut rng);
+        // 3D frames are also written as VTK files, which are a lot larger
+        run3(&rule, frame, imgdim, max_iters / 10);
+        return;
+    }
+    if arg.as_ref().is_some_and(|s| { s == "hex" }) {
+        let mut frame = Frame::new(side, side);
+        random_init_frame(&mut frame, &mut rng);
+        run(&HexRainbowLife, frame, seed, imgdim, scale, max_iters);
+        return;
+    }
+    if let Some(s) = arg {
+        let schedule = match s.parse::<Schedule<LifeLike>>() {
+            Ok(schedule) => schedule,
+            Err(e) => {
+                println!("{}", e);
+                return;
+            },
+        };
+        let mut frame = Frame::new(side, side);
+        random_life_frame(&mut frame, &mut rng);
+        run(&schedule, frame, seed, imgdim, scale, max_iters);
+        return;
+    }
+
     // create the frame
-    let mut sim = simulation::Frame::new(side, side);
-    random_init_frame(&mut sim);
+    let mut frame = simulation::Frame::new(side, side);
+    random_init_frame(&mut frame, &mut rng);
+
+    run(&RainbowLife, frame, seed, imgdim, scale, max_iters);
+}
+
+/// Run a simulation with a rule writing each frame into the files directory
+fn run<R>(rule: &R, frame: Frame<R::State>, seed: u64, imgdim: u32,
+          scale: u32, max_iters: usize)
+where R: Rule + Sync,
+      R::State: Clone + Default + PartialEq + Send + Sync,
+      W<R::State>: Into<Color> {
+    println!("running {}", rule.name());
+    let threads = std::thread::available_parallelism().map_or(1, |n| n.get());
+    let shape = match rule.neighborhood() {
+        Neighborhood::Hexagonal(_) => Shape::Hexagon,
+        ref hood if hood.is_triangular() => Shape::Triangle,
+        _ => Shape::Blurred,
+    };
+    let mut sim = Simulation::seeded(frame, seed);
 
     // setup directory to contain images
     std::fs::create_dir_all("files").unwrap();
 
     for n in 0..max_iters {
-        // write the image into a buffer
-        let mut buf = image::ImageBuffer::new(imgdim, imgdim);
-        for (x, y, pixel) in buf.enumerate_pixels_mut() {
-            let v = sim.get((x / scale) as usize, (y / scale) as usize);
-            *pixel = W(*v).into();
+        let name = format!("files/{:03}.png", n);
+        save(sim.frame(), &name, imgdim, scale, shape);
+
+        // advance to the next frame
+        sim.advance_parallel(threads, rule);
+    }
+}
+
+/// How the squares of a frame are drawn
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Shape {
+    /// squares that are blurred together
+    Blurred,
+    /// squares with sharp edges
+    Sharp,
+    /// hexagons of a hexagonal lattice
+    Hexagon,
+    /// triangles of a triangular lattice
+    Triangle,
+}
+
+/// Draw a frame with every square scale pixels wide and save it as a PNG
+fn save<T>(frame: &Frame<T>, name: &str, imgdim: u32, scale: u32, shape: Shape)
+where T: Clone,
+      W<T>: Into<Color> {
+    // write the image into a buffer
+    let mut buf = image::ImageBuffer::new(imgdim, imgdim);
+    for (x, y, pixel) in buf.enumerate_pixels_mut() {
+        let v = match shape {
+            Shape::Hexagon => {
+                let (q, r) = hexagon_at(x, y, scale);
+                frame.at(q, r)
+            },
+            Shape::Triangle => {
+                let (i, j) = triangle_at(x, y, scale);
+                frame.at(i, j)
+            },
+            Shape::Blurred | Shape::Sharp =>
+                frame.get((x / scale) as usize, (y / scale) as usize),
+        };
+        *pixel = W(v.clone()).into();
+    }
+
+    // save the image, hexagons and triangles are drawn sharp so that their
+    // shape shows
+    let mut fout = File::create(Path::new(name)).unwrap();
+    let img = image::ImageRgb8(buf);
+    let img = if shape == Shape::Blurred { img.blur(10.) } else { img };
+    let _ = img.save(&mut fout, image::PNG);
+}
+
+/// Advance the same rule on several graphs and print the fraction of alive
+/// nodes in each of them every 10 generations
+fn compare_graphs(rule: &LifeLike,
+                  graphs: Vec<(&str, Graph<game_of_life::State>)>,
+                  generations: usize) {
+    println!("running {}", rule.name());
+    let names = graphs.iter().map(|g| { format!("{:>16}", g.0) })
+        .collect::<String>();
+    println!("generation{}", names);
+    let mut graphs = graphs.into_iter().map(|g| { g.1 }).collect::<Vec<_>>();
+    for n in 0..generations + 1 {
+        if n % 10 == 0 {
+            let densities = graphs.iter().map(|g| {
+                let alive = g.states().iter()
+                    .filter(|&&e| { e == game_of_life::State::Alive })
+                    .count();
+                format!("{:>16.4}", alive as f64 / g.len().max(1) as f64)
+            }).collect::<String>();
+            println!("{:>10}{}", n, densities);
         }
+        graphs = graphs.iter().map(|g| { g.advance(rule) }).collect();
+    }
+}
 
-        // save the image
-        let ref name = format!("files/{:03}.png", n);
-        let ref mut fout = File::create(&Path::new(name)).unwrap();
-        let _ = image::ImageRgb8(buf).blur(10.).save(fout, image::PNG);
+/// A graph with the states of the nodes of another graph with as many nodes
+fn with_states<T>(mut graph: Graph<T>, states: &Graph<T>) -> Graph<T>
+where T: Clone {
+    for (i, state) in states.states().iter().enumerate() {
+        *graph.get_mut(i) = state.clone();
+    }
+    graph
+}
 
-        // advance to the next frame
-        sim = sim.next_frame(rainbow_life::rule);
+/// Run a 3D simulation with a rule writing each frame into the files
+/// directory both as a picture and as a VTK file
+fn run3<R>(rule: &R, frame: Frame3<R::State>, imgdim: u32, max_iters: usize)
+where R: Rule3,
+      R::State: Clone + Default + PartialEq + FiniteState,
+      W<R::State>: Into<Color> {
+    println!("running {}", rule.name());
+    let mut frame = frame;
+
+    // setup directory to contain images
+    std::fs::create_dir_all("files").unwrap();
+
+    for n in 0..max_iters {
+        let name = format!("files/{:03}.png", n);
+        let mut fout = File::create(Path::new(&name)).unwrap();
+        let img = image::ImageRgb8(render_voxels(&frame, imgdim));
+        let _ = img.save(&mut fout, image::PNG);
+
+        let name = format!("files/{:03}.vtk", n);
+        let fout = File::create(Path::new(&name)).unwrap();
+        let title = format!("{} generation {}", rule.name(), n);
+        frame.write_vtk(std::io::BufWriter::new(fout), &title).unwrap();
+
+        frame = frame.advance(rule);
     }
 }
 
+/// Draw the cubes of a frame that aren't in their default state as shaded
+/// cubes seen from above at an angle, with the x axis going down to the right,
+/// the y axis going down to the left and the z axis going up
+fn render_voxels<T>(frame: &Frame3<T>, imgdim: u32)
+    -> image::ImageBuffer<Color, Vec<u8>>
+where T: Clone + Default + PartialEq,
+      W<T>: Into<Color> {
+    let mut buf = image::ImageBuffer::new(imgdim, imgdim);
+    let n = frame.width().max(frame.height()).max(frame.depth()).max(1);
+    // every cube is drawn within a square that is 2 * a pixels wide
+    let a = (imgdim as usize / (2 * n)).max(1);
+
+    // cubes further back are drawn first so that the ones in front cover them
+    let mut cubes = frame.enumerate_cubes()
+        .filter(|&(_, _, _, cube)| { *cube != T::default() })
+        .collect::<Vec<_>>();
+    cubes.sort_by_key(|&(x, y, z, _)| { x + y + z });
+    for (x, y, z, cube) in cubes {
+        let color: Color = W(cube.clone()).into();
+        let [r, g, b] = color.data;
+        let left = (x + n - 1 - y) * a;
+        let top = (n - 1 - z) * a + (x + y) * a / 2;
+        for j in 0..2 * a {
+            for i in 0..2 * a {
+                let shade = match cube_face(i, j, a) {
+                    Some(shade) => shade,
+                    None => continue,
+                };
+                let (px, py) = ((left + i) as u32, (top + j) as u32);
+                if px < imgdim && py < imgdim {
+                    let s = |c: u8| { (c as f64 * shade) as u8 };
+                    buf.put_pixel(px, py, image::Rgb([s(r), s(g), s(b)]));
+                }
+            }
+        }
+    }
+    buf
+}
+
+/// How brightly the pixel at (i, j) within the square that a cube is drawn in
+/// is lit, or None if the pixel is outside of the cube. The top of the cube is
+/// a rhombus across the top half of the square that is lit the most, below it
+/// are the left and right faces
+fn cube_face(i: usize, j: usize, a: usize) -> Option<f64> {
+    let a = a as f64;
+    let (u, v) = (i as f64 + 0.5 - a, j as f64 + 0.5);
+    if u.abs() / a + (v - a / 2.0).abs() / (a / 2.0) <= 1.0 {
+        Some(1.0)
+    } else if v >= a - u.abs() / 2.0 && v <= 2.0 * a - u.abs() / 2.0 {
+        Some(if u < 0.0 { 0.75 } else { 0.5 })
+    } else {
+        None
+    }
+}
+
+/// The axial coordinate of the hexagon that covers a pixel, where hexagons are
+/// scale pixels wide with a point at the top and the hexagon at (0, 0) is
+/// centered on the top left corner. Rows of hexagons are shifted half a
+/// hexagon further to the right each, so the coordinates go past the edges of
+/// the frame, which wraps them around
+fn hexagon_at(x: u32, y: u32, scale: u32) -> (isize, isize) {
+    let sqrt3 = 3f64.sqrt();
+    let size = scale as f64 / sqrt3;
+    let (x, y) = (x as f64 + 0.5, y as f64 + 0.5);
+    let q = (sqrt3 / 3.0 * x - y / 3.0) / size;
+    let r = 2.0 / 3.0 * y / size;
+
+    // round to the nearest hexagon in cube coordinates, where q + r + s = 0,
+    // by fixing up the coordinate that was rounded the most
+    let s = -q - r;
+    let (mut rq, mut rr, rs) = (q.round(), r.round(), s.round());
+    let (dq, dr, ds) = ((rq - q).abs(), (rr - r).abs(), (rs - s).abs());
+    if dq > dr && dq > ds {
+        rq = -rr - rs;
+    } else if dr > ds {
+        rr = -rq - rs;
+    }
+    (rq as isize, rr as isize)
+}
+
+/// The coordinate of the triangle that covers a pixel, where triangles have
+/// sides that are 2 * scale pixels long, so that each column of triangles is
+/// as wide as a column of squares, and the triangle at (0, 0) points up with
+/// its left corner at the left edge. The coordinates can go past the bottom of
+/// the frame, which wraps them around
+fn triangle_at(x: u32, y: u32, scale: u32) -> (isize, isize) {
+    let height = scale as f64 * 3f64.sqrt();
+    let (u, v) = ((x as f64 + 0.5) / scale as f64, (y as f64 + 0.5) / height);
+    let (column, row) = (u.floor(), v.floor());
+    let (f, v) = (u - column, v - row);
+    let (column, row) = (column as isize, row as isize);
+
+    // the pixel is between the left corner of the triangle in its column and
+    // the triangle to the left of it, which are split by a diagonal edge
+    let inside = if (column + row) % 2 == 0 { v > 1.0 - f } else { v < f };
+    (if inside { column } else { column - 1 }, row)
+}
+
 /// Fill a frame
-fn random_init_frame(mut frame: &mut simulation::Frame<State>) {
+fn random_init_frame<G>(frame: &mut simulation::Frame<State>, rng: &mut G)
+where G: Rng {
     for x in 0..frame.width() {
         for y in 0..frame.height() {
-            *frame.get_mut(x, y) = match rand::thread_rng().gen_range(0, 4) {
+            *frame.get_mut(x, y) = match rng.gen_range(0, 4) {
                 0 => State::Red,
                 1 => State::Green,
                 2 => State::Blue,
@@ -62,14 +377,44 @@ fn random_init_frame(mut frame: &mut simulation::Frame<State>) {
     }
 }
 
+/// Fill a frame with squares that are alive about a third of the time
+fn random_life_frame<G>(frame: &mut Frame<game_of_life::State>, rng: &mut G)
+where G: Rng {
+    for x in 0..frame.width() {
+        for y in 0..frame.height() {
+            if rng.gen_range(0, 3) == 0 {
+                *frame.get_mut(x, y) = game_of_life::State::Alive;
+            }
+        }
+    }
+}
+
+/// Fill the middle of a 3D frame with cubes that are alive about a third of
+/// the time
+fn random_life_frame3<G>(frame: &mut Frame3<game_of_life::State>, rng: &mut G)
+where G: Rng {
+    let (w, h, d) = (frame.width(), frame.height(), frame.depth());
+    for z in d / 4..d - d / 4 {
+        for y in h / 4..h - h / 4 {
+            for x in w / 4..w - w / 4 {
+                if rng.gen_range(0, 3) == 0 {
+                    *frame.get_mut(x, y, z) = game_of_life::State::Alive;
+                }
+            }
+        }
+    }
+}
+
 /// Fill a 20 by 20 region of a frame
 #[allow(dead_code)]
-fn fill_rect(mut frame: &mut simulation::Frame<State>, x: usize, y: usize) {
-    let W(fill) = rand::thread_rng().gen();
+fn fill_rect<G>(frame: &mut simulation::Frame<State>, x: usize, y: usize,
+                rng: &mut G)
+where G: Rng {
+    let W(fill) = rng.gen();
     for i in 0..20 {
         for j in 0..20 {
             *frame.get_mut(x + i, y + j) =
-                if rand::thread_rng().gen() { fill }
+                if rng.gen() { fill }
                 else { State::Dead }
         }
     }
@@ -103,9 +448,9 @@ impl Rand for W<State> {
     }
 }
 
-impl Into<Color> for W<State> {
-    fn into(self) -> Color {
-        match self {
+impl From<W<State>> for Color {
+    fn from(state: W<State>) -> Color {
+        match state {
             W(State::Red)   => image::Rgb([255, 0, 0]),
             W(State::Blue)  => image::Rgb([0, 255, 0]),
             W(State::Green) => image::Rgb([0, 0, 255]),
@@ -113,3 +458,27 @@ impl Into<Color> for W<State> {
         }
     }
 }
+
+impl From<W<game_of_life::State>> for Color {
+    fn from(state: W<game_of_life::State>) -> Color {
+        match state {
+            W(game_of_life::State::Alive) => image::Rgb([255, 255, 255]),
+            W(game_of_life::State::Dead)  => image::Rgb([0, 0, 0]),
+        }
+    }
+}
+
+impl From<W<u8>> for Color {
+    fn from(state: W<u8>) -> Color {
+        // dead cells are white and the first few states are black, red,
+        // green and blue, like diagrams that are printed
+        match state {
+            W(0) => image::Rgb([255, 255, 255]),
+            W(1) => image::Rgb([0, 0, 0]),
+            W(2) => image::Rgb([255, 0, 0]),
+            W(3) => image::Rgb([0, 255, 0]),
+            W(4) => image::Rgb([0, 0, 255]),
+            W(n) => image::Rgb([n, n, n]),
+        }
+    }
+}
@@ -0,0 +1,311 @@
+use super::{Frame, Square};
+
+/// The set of points, relative to a square, that make up its neighbors
+#[derive(Clone, Debug, PartialEq)]
+pub enum Neighborhood {
+    /// Every point within a square of the given radius
+    Moore(usize),
+    /// Every point within the given Manhattan distance
+    VonNeumann(usize),
+    /// Every point within the given Euclidean distance
+    Circular(usize),
+    /// Every point in the same row or column within the given distance
+    Cross(usize),
+    /// Every point within the given distance on a hexagonal lattice. Frames
+    /// store hexagonal lattices in axial coordinates, where the row below a
+    /// hexagon is shifted half a hexagon to the right, so its six neighbors
+    /// are at (±1, 0), (0, ±1), (1, -1) and (-1, 1)
+    Hexagonal(usize),
+    /// The 3 triangles that share an edge with a triangle on a triangular
+    /// lattice. Frames store triangular lattices with the triangle at (x, y)
+    /// pointing up if x + y is even and down otherwise, so the triangles to its
+    /// left and right point the other way. Offsets are given for triangles
+    /// that point up, triangles that point down see them flipped vertically.
+    ///
+    /// Triangles only line up across the edges of a frame whose boundary is
+    /// constant, or toroidal with an even width and height. Anywhere else,
+    /// like across a reflecting or Klein bottle boundary or on a torus with
+    /// an odd side, a triangle past an edge points the wrong way, so the
+    /// triangles there don't see each other as neighbors both ways. The same
+    /// goes for the other triangular neighborhoods
+    TriangularEdges,
+    /// The 9 triangles that only share a vertex with a triangle on a
+    /// triangular lattice
+    TriangularVertices,
+    /// The 12 triangles that share an edge or a vertex with a triangle on a
+    /// triangular lattice
+    Triangular,
+    /// An arbitrary list of offsets
+    Custom(Vec<(isize, isize)>),
+}
+
+/// The Moore neighborhood of radius 1 used by Conway's Game of Life
+pub const MOORE: Neighborhood = Neighborhood::Moore(1);
+
+/// The six neighbors of a hexagon on a hexagonal lattice
+pub const HEXAGONAL: Neighborhood = Neighborhood::Hexagonal(1);
+
+impl Neighborhood {
+    /// The largest distance along either axis of any neighbor
+    pub fn radius(&self) -> usize {
+        use self::Neighborhood::*;
+        match *self {
+            Moore(r) | VonNeumann(r) | Circular(r) | Cross(r)
+                | Hexagonal(r) => r,
+            TriangularEdges => 1,
+            TriangularVertices | Triangular => 2,
+            Custom(ref offsets) => offsets.iter()
+                .map(|&(i, j)| { i.unsigned_abs().max(j.unsigned_abs()) })
+                .max()
+                .unwrap_or(0),
+        }
+    }
+
+    /// The neighborhood that every one of a list of neighborhoods is, if they
+    /// are all the same, otherwise the smallest Moore neighborhood that covers
+    /// all of them
+    pub fn covering<I>(hoods: I) -> Neighborhood
+    where I: IntoIterator<Item = Neighborhood> {
+        let hoods = hoods.into_iter().collect::<Vec<_>>();
+        match hoods.first() {
+            Some(first) if hoods.iter().all(|h| { h == first }) => first.clone(),
+            _ => Neighborhood::Moore(hoods.iter().map(|h| { h.radius() })
+                                     .max()
+                                     .unwrap_or(0)),
+        }
+    }
+
+    /// Whether the offset (i, j) is a neighbor. The origin is never a neighbor
+    /// unless it is explicitly part of a custom neighborhood
+    pub fn contains(&self, i: isize, j: isize) -> bool {
+        use self::Neighborhood::*;
+        let (a, b) = (i.unsigned_abs(), j.unsigned_abs());
+        match *self {
+            Custom(ref offsets) => offsets.contains(&(i, j)),
+            _ if a == 0 && b == 0 => false,
+            Moore(r) => a <= r && b <= r,
+            VonNeumann(r) => a + b <= r,
+            Circular(r) => a * a + b * b <= r * r,
+            Cross(r) => (a == 0 || b == 0) && a + b <= r,
+            Hexagonal(r) => a <= r && b <= r && (i + j).unsigned_abs() <= r,
+            TriangularEdges => (j == 0 && a == 1) || (i == 0 && j == 1),
+            TriangularVertices => Triangular.contains(i, j)
+                && !TriangularEdges.contains(i, j),
+            // the triangles in the row above touch the top vertex, the ones in
+            // the row below touch the bottom edge
+            Triangular => match j {
+                -1 => a <= 1,
+                0 | 1 => a <= 2,
+                _ => false,
+            },
+        }
+    }
+
+    /// Whether the neighborhood is on a triangular lattice, where triangles
+    /// that point down see the offsets flipped vertically
+    pub fn is_triangular(&self) -> bool {
+        use self::Neighborhood::*;
+        matches!(*self, TriangularEdges | TriangularVertices | Triangular)
+    }
+
+    /// The number of neighbors in the neighborhood
+    pub fn len(&self) -> usize {
+        self.offsets().count()
+    }
+
+    /// Whether there are no neighbors in the neighborhood
+    pub fn is_empty(&self) -> bool {
+        self.offsets().next().is_none()
+    }
+
+    /// Iterate over the offsets of every neighbor
+    pub fn offsets(&self) -> Offsets<'_> {
+        Offsets {
+            neighborhood: self,
+            index: 0,
+        }
+    }
+}
+
+/// An iterator over the offsets in a Neighborhood
+#[derive(Debug, Clone)]
+pub struct Offsets<'a> {
+    neighborhood: &'a Neighborhood,
+    index: usize,
+}
+
+impl<'a> Iterator for Offsets<'a> {
+    type Item = (isize, isize);
+
+    fn next(&mut self) -> Option<(isize, isize)> {
+        if let Neighborhood::Custom(ref offsets) = *self.neighborhood {
+            self.index += 1;
+            return offsets.get(self.index - 1).cloned();
+        }
+
+        // scan the bounding square of the neighborhood for offsets within it
+        let r = self.neighborhood.radius() as isize;
+        let side = (2 * r + 1) as usize;
+        while self.index < side * side {
+            let i = (self.index % side) as isize - r;
+            let j = (self.index / side) as isize - r;
+            self.index += 1;
+            if self.neighborhood.contains(i, j) {
+                return Some((i, j));
+            }
+        }
+        None
+    }
+}
+
+/// An iterator over the values of the neighbors of a square
+#[derive(Debug, Clone)]
+pub struct Neighbors<'a, 'b, T>
+where T: 'a {
+    frame: &'a Frame<T>,
+    point: (isize, isize),
+    offsets: Offsets<'b>,
+    /// whether the offsets are flipped vertically for a triangle pointing down
+    flip: bool,
+}
+
+impl<'a, 'b, T> Iterator for Neighbors<'a, 'b, T>
+where T: 'a {
+    type Item = &'a T;
+
+    fn next(&mut self) -> Option<&'a T> {
+        let (x, y) = self.point;
+        let flip = self.flip;
+        self.offsets.next().map(|(i, j)| {
+            self.frame.at(x + i, if flip { y - j } else { y + j })
+        })
+    }
+}
+
+impl<'a, T> Square<'a, T>
+where T: 'a {
+    /// Iterate over the values of the square's neighbors
+    pub fn neighbors<'b>(&self, neighborhood: &'b Neighborhood)
+        -> Neighbors<'a, 'b, T> {
+        let (x, y) = self.coordinate();
+        Neighbors {
+            frame: self.frame(),
+            point: (x as isize, y as isize),
+            offsets: neighborhood.offsets(),
+            flip: neighborhood.is_triangular() && !self.points_up(),
+        }
+    }
+
+    /// The number of neighbors that satisfy a predicate
+    pub fn count_neighbors<P>(&self, neighborhood: &Neighborhood, pred: P)
+        -> usize
+    where P: Fn(&T) -> bool {
+        self.neighbors(neighborhood).filter(|e| { pred(e) }).count()
+    }
+
+    /// Which neighbors satisfy a predicate, as a bit set where bit i is set if
+    /// the neighbor at the i-th offset of the neighborhood does. Only the first
+    /// 64 neighbors are included
+    pub fn neighbor_pattern<P>(&self, neighborhood: &Neighborhood, pred: P)
+        -> u64
+    where P: Fn(&T) -> bool {
+        self.neighbors(neighborhood)
+            .take(64)
+            .enumerate()
+            .fold(0, |acc, (i, e)| {
+                if pred(e) { acc | 1 << i } else { acc }
+            })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::Frame;
+    use super::Neighborhood;
+    use super::Neighborhood::*;
+
+    #[test]
+    fn sizes() {
+        assert_eq!(Moore(1).len(), 8);
+        assert_eq!(Moore(2).len(), 24);
+        assert_eq!(VonNeumann(1).len(), 4);
+        assert_eq!(VonNeumann(2).len(), 12);
+        assert_eq!(Circular(2).len(), 12);
+        assert_eq!(Cross(2).len(), 8);
+        assert_eq!(Hexagonal(1).len(), 6);
+        assert_eq!(Hexagonal(2).len(), 18);
+        assert_eq!(TriangularEdges.len(), 3);
+        assert_eq!(TriangularVertices.len(), 9);
+        assert_eq!(Triangular.len(), 12);
+        assert_eq!(Custom(vec![(0, 1), (2, 0)]).len(), 2);
+        assert!(Moore(0).is_empty());
+    }
+
+    #[test]
+    fn hexagonal() {
+        let hood = Hexagonal(1);
+        assert_eq!(hood.radius(), 1);
+        assert!(hood.contains(1, -1) && hood.contains(-1, 1));
+        assert!(!hood.contains(1, 1) && !hood.contains(-1, -1));
+        assert!(Hexagonal(2).contains(2, -1) && !Hexagonal(2).contains(2, 1));
+    }
+
+    #[test]
+    fn triangular() {
+        // a triangle pointing up and one pointing down next to it
+        let mut frame = Frame::<i32>::new(6, 6);
+        *frame.get_mut(2, 3) = 1;
+        *frame.get_mut(3, 1) = 1;
+        let (up, down) = (frame.square(2, 2), frame.square(3, 2));
+        assert!(up.points_up() && !down.points_up());
+        assert_eq!(up.count_neighbors(&TriangularEdges, |e| { *e == 1 }), 1);
+        assert_eq!(down.count_neighbors(&TriangularEdges, |e| { *e == 1 }), 1);
+        assert_eq!(up.count_neighbors(&Triangular, |e| { *e == 1 }), 2);
+        assert_eq!(down.count_neighbors(&TriangularVertices, |e| { *e == 1 }),
+                   1);
+        assert_eq!(Triangular.radius(), 2);
+        assert!(Triangular.is_triangular() && !Hexagonal(1).is_triangular());
+    }
+
+    #[test]
+    fn custom() {
+        let hood = Neighborhood::Custom(vec![(1, 0), (0, -3)]);
+        assert_eq!(hood.radius(), 3);
+        assert_eq!(hood.offsets().collect::<Vec<_>>(), vec![(1, 0), (0, -3)]);
+        assert!(hood.contains(0, -3));
+        assert!(!hood.contains(0, 3));
+    }
+
+    #[test]
+    fn count() {
+        let mut frame = Frame::<i32>::new(5, 5);
+        *frame.get_mut(1, 2) = 1;
+        *frame.get_mut(3, 3) = 1;
+        *frame.get_mut(2, 0) = 1;
+        let square = frame.square(2, 2);
+        assert_eq!(square.count_neighbors(&Moore(1), |e| { *e == 1 }), 2);
+        assert_eq!(square.count_neighbors(&VonNeumann(1), |e| { *e == 1 }), 1);
+        assert_eq!(square.count_neighbors(&Cross(2), |e| { *e == 1 }), 2);
+    }
+
+    #[test]
+    fn pattern() {
+        let mut frame = Frame::<i32>::new(3, 3);
+        *frame.get_mut(0, 0) = 1;
+        *frame.get_mut(2, 1) = 1;
+        *frame.get_mut(1, 2) = 1;
+        let square = frame.square(1, 1);
+        // the offsets are in row major order: NW N NE W E SW S SE
+        let pattern = square.neighbor_pattern(&Moore(1), |e| { *e == 1 });
+        assert_eq!(pattern, 0b0101_0001);
+    }
+
+    #[test]
+    fn radius_larger_than_frame() {
+        // on a torus large neighborhoods wrap around multiple times
+        let frame = Frame::<i32>::new(2, 2);
+        let square = frame.square(0, 0);
+        assert_eq!(square.neighbors(&Moore(3)).count(), 48);
+        assert_eq!(square.within_ortholinear(3).len(), 48);
+    }
+}
@@ -0,0 +1,337 @@
+use super::{Boundary, Frame, ParseRuleError};
+use super::rule::split_field;
+
+use std::fmt;
+use std::str::FromStr;
+
+/// A rule that computes the next state of a cell in a row from the cells
+/// within its radius. States are numbered from 0 up to the number of states
+pub trait Rule1 {
+    /// A human readable name for the rule
+    fn name(&self) -> &str;
+
+    /// How many cells on either side of a cell the rule looks at
+    fn radius(&self) -> usize {
+        1
+    }
+
+    /// The number of states that a cell can be in
+    fn state_count(&self) -> usize {
+        2
+    }
+
+    /// Compute the next state of the cell in the middle of the given cells,
+    /// which are the 2 * radius + 1 cells around it from left to right
+    fn step(&self, cells: &[u8]) -> u8;
+}
+
+impl<R> Rule1 for &R
+where R: Rule1 + ?Sized {
+    fn name(&self) -> &str { (**self).name() }
+    fn radius(&self) -> usize { (**self).radius() }
+    fn state_count(&self) -> usize { (**self).state_count() }
+
+    fn step(&self, cells: &[u8]) -> u8 {
+        (**self).step(cells)
+    }
+}
+
+/// One of Wolfram's 256 elementary rules, where the next state of a cell is
+/// bit 4 * left + 2 * middle + right of the rule's number
+#[derive(Clone, Debug, PartialEq)]
+pub struct Elementary {
+    name: String,
+    number: u8,
+}
+
+impl Elementary {
+    /// Create the rule with the given Wolfram number
+    pub fn new(number: u8) -> Elementary {
+        Elementary { name: format!("Rule {}", number), number }
+    }
+
+    /// The Wolfram number of the rule
+    pub fn number(&self) -> u8 {
+        self.number
+    }
+}
+
+impl FromStr for Elementary {
+    type Err = ParseRuleError;
+
+    /// Parse a rule number like "30", "W30" or "Rule 30"
+    fn from_str(rulestring: &str) -> Result<Elementary, ParseRuleError> {
+        let upper = rulestring.trim().to_ascii_uppercase();
+        let number = upper.strip_prefix("RULE")
+            .or_else(|| { upper.strip_prefix('W') })
+            .unwrap_or(&upper);
+        number.trim().parse().map(Elementary::new).map_err(|_| {
+            ParseRuleError::new(rulestring,
+                                "expected a rule number between 0 and 255")
+        })
+    }
+}
+
+impl fmt::Display for Elementary {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "W{}", self.number)
+    }
+}
+
+impl Rule1 for Elementary {
+    fn name(&self) -> &str { &self.name }
+
+    fn step(&self, cells: &[u8]) -> u8 {
+        let i = cells.iter().fold(0, |acc, &c| { acc << 1 | (c & 1) });
+        self.number >> i & 1
+    }
+}
+
+/// The largest radius of a totalistic rule, which keeps its table of next
+/// states to a reasonable size
+pub const MAX_RADIUS: usize = 1000;
+
+/// A totalistic rule over k states and a radius r, where the next state of a
+/// cell depends only on the sum of the states of the 2r + 1 cells around it.
+/// The rule is given by Wolfram's code, whose digit n in base k is the next
+/// state for a sum of n
+#[derive(Clone, Debug, PartialEq)]
+pub struct Totalistic {
+    name: String,
+    states: usize,
+    radius: usize,
+    code: u64,
+    /// the next state for every sum
+    table: Vec<u8>,
+}
+
+impl Totalistic {
+    /// Create a rule from its number of states, its radius and its code.
+    /// Panics if there are fewer than 2 or more than 256 states, the radius is
+    /// larger than MAX_RADIUS or the code has more digits than there are sums
+    pub fn new(states: usize, radius: usize, code: u64) -> Totalistic {
+        assert!((2..=256).contains(&states),
+                "there must be between 2 and 256 states");
+        assert!(radius <= MAX_RADIUS, "the radius must be at most {}",
+                MAX_RADIUS);
+        let sums = (2 * radius + 1) * (states - 1) + 1;
+        let mut table = Vec::with_capacity(sums);
+        let mut rest = code;
+        for _ in 0..sums {
+            table.push((rest % states as u64) as u8);
+            rest /= states as u64;
+        }
+        assert!(rest == 0, "the code {} has more digits than there are sums",
+                code);
+        let name = format!("K{},R{},C{}", states, radius, code);
+        Totalistic { name, states, radius, code, table }
+    }
+
+    /// Wolfram's code for the rule
+    pub fn code(&self) -> u64 {
+        self.code
+    }
+}
+
+impl FromStr for Totalistic {
+    type Err = ParseRuleError;
+
+    /// Parse a rulestring like "K3,R1,C777" with the number of states, the
+    /// radius and the code. The number of states defaults to 2 and the radius
+    /// to 1
+    fn from_str(rulestring: &str) -> Result<Totalistic, ParseRuleError> {
+        let error = |reason: &str| { ParseRuleError::new(rulestring, reason) };
+        let (mut states, mut radius, mut code) = (2, 1, None);
+        for field in rulestring.split(',').map(str::trim) {
+            let (key, value) = split_field(field);
+            let number = value.trim().parse::<u64>().map_err(|_| {
+                error(&format!("{:?} is not a number", value))
+            })?;
+            match key {
+                Some('K') => states = number,
+                Some('R') => radius = number,
+                Some('C') => code = Some(number),
+                _ => return Err(error(&format!("unknown field {:?}", field))),
+            }
+        }
+        let code = code.ok_or_else(|| { error("expected a code") })?;
+        if !(2..=256).contains(&states) {
+            return Err(error("there must be between 2 and 256 states"));
+        }
+        if radius > MAX_RADIUS as u64 {
+            return Err(error(&format!("the radius must be at most {}",
+                                      MAX_RADIUS)));
+        }
+        let (states, radius) = (states as usize, radius as usize);
+        let sums = (2 * radius + 1) * (states - 1) + 1;
+        let fits = match (states as u64).checked_pow(sums as u32) {
+            Some(limit) => code < limit,
+            None => true,
+        };
+        if !fits {
+            return Err(error(&format!("the code has more than {} digits in \
+                                       base {}", sums, states)));
+        }
+        Ok(Totalistic::new(states, radius, code))
+    }
+}
+
+impl fmt::Display for Totalistic {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.name)
+    }
+}
+
+impl Rule1 for Totalistic {
+    fn name(&self) -> &str { &self.name }
+    fn radius(&self) -> usize { self.radius }
+    fn state_count(&self) -> usize { self.states }
+
+    fn step(&self, cells: &[u8]) -> u8 {
+        let sum = cells.iter().map(|&c| { c as usize }).sum::<usize>();
+        self.table[sum]
+    }
+}
+
+/// The next generation of a row of cells, where the boundary condition
+/// decides what lies past either end of the row
+pub fn next_row<R>(rule: &R, row: &[u8], boundary: &Boundary<u8>) -> Vec<u8>
+where R: Rule1 + ?Sized {
+    let r = rule.radius() as isize;
+    let at = |x: isize| {
+        match boundary.resolve(x, 0, row.len(), 1) {
+            Some((x, _)) => row[x],
+            None => match *boundary {
+                Boundary::Constant(value) => value,
+                _ => unreachable!(),
+            },
+        }
+    };
+    let mut cells = Vec::with_capacity(2 * r as usize + 1);
+    (0..row.len() as isize).map(|x| {
+        cells.clear();
+        cells.extend((x - r..x + r + 1).map(&at));
+        rule.step(&cells)
+    }).collect()
+}
+
+/// The spacetime diagram of a row of cells, a frame whose rows are the given
+/// number of generations starting with the row itself, so that it can be drawn
+/// like any other frame. The frame has the boundary condition of the row
+pub fn spacetime<R>(rule: &R, row: &[u8], generations: usize,
+                    boundary: Boundary<u8>) -> Frame<u8>
+where R: Rule1 + ?Sized {
+    let mut frame = Frame::new(row.len(), generations);
+    let mut row = row.to_vec();
+    for y in 0..generations {
+        for (x, &cell) in row.iter().enumerate() {
+            *frame.get_mut(x, y) = cell;
+        }
+        row = next_row(rule, &row, &boundary);
+    }
+    frame.with_boundary(boundary)
+}
+
+/// A row of dead cells with a single cell in the given state in the middle,
+/// which is where diagrams usually start
+pub fn single(width: usize, state: u8) -> Vec<u8> {
+    let mut row = vec![0; width];
+    if width > 0 {
+        row[width / 2] = state;
+    }
+    row
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::Boundary;
+    use super::{next_row, single, spacetime, Elementary, Rule1, Totalistic};
+
+    /// The rows of a diagram as strings of digits
+    fn rows<R>(rule: &R, row: &[u8], generations: usize) -> Vec<String>
+    where R: Rule1 {
+        let frame = spacetime(rule, row, generations, Boundary::Toroidal);
+        (0..frame.height()).map(|y| {
+            (0..frame.width()).map(|x| { frame.get(x, y).to_string() })
+                .collect()
+        }).collect()
+    }
+
+    #[test]
+    fn rule_30() {
+        assert_eq!(rows(&Elementary::new(30), &single(7, 1), 4),
+                   ["0001000", "0011100", "0110010", "1101111"]);
+    }
+
+    #[test]
+    fn rule_90() {
+        // rule 90 draws Sierpinski's triangle, whose row 2^n has two cells
+        let diagram = rows(&Elementary::new(90), &single(17, 1), 9);
+        assert_eq!(diagram[4], "00001000000010000");
+        assert_eq!(diagram[8], "10000000000000001");
+    }
+
+    #[test]
+    fn rule_110() {
+        assert_eq!(rows(&Elementary::new(110), &single(8, 1), 4),
+                   ["00001000", "00011000", "00111000", "01101000"]);
+    }
+
+    #[test]
+    fn boundaries() {
+        let rule = Elementary::new(30);
+        let row = [1, 1, 0, 0, 0];
+        assert_eq!(next_row(&rule, &row, &Boundary::Toroidal),
+                   [1, 0, 1, 0, 1]);
+        assert_eq!(next_row(&rule, &row, &Boundary::Constant(0)),
+                   [1, 0, 1, 0, 0]);
+        assert_eq!(next_row(&rule, &row, &Boundary::Constant(1)),
+                   [0, 0, 1, 0, 1]);
+        assert_eq!(next_row(&rule, &row, &Boundary::Reflecting),
+                   [0, 0, 1, 0, 0]);
+    }
+
+    #[test]
+    fn totalistic() {
+        // the cells that are born with a sum of 1 or 2 are those of rule 126
+        let rule = "K2,R1,C6".parse::<Totalistic>().unwrap();
+        let row = [0, 1, 1, 0, 1, 0, 0, 0, 1, 1, 1, 0, 0, 1];
+        assert_eq!(rows(&rule, &row, 10),
+                   rows(&Elementary::new(126), &row, 10));
+
+        // 777 is 1001210 in base 3
+        let rule = "k3,r1,c777".parse::<Totalistic>().unwrap();
+        assert_eq!(rule, Totalistic::new(3, 1, 777));
+        assert_eq!(rule.name(), "K3,R1,C777");
+        assert_eq!(rule.step(&[0, 1, 0]), 1);
+        assert_eq!(rule.step(&[1, 0, 1]), 2);
+        assert_eq!(rule.step(&[1, 1, 1]), 1);
+        assert_eq!(rule.step(&[2, 2, 2]), 1);
+        assert_eq!(rule.step(&[2, 1, 2]), 0);
+
+        let rule = "C10,R2".parse::<Totalistic>().unwrap();
+        assert_eq!((rule.state_count(), rule.radius()), (2, 2));
+        assert_eq!(rule.step(&[1, 0, 0, 0, 0]), 1);
+    }
+
+    #[test]
+    fn parse() {
+        assert_eq!("30".parse::<Elementary>().unwrap(), Elementary::new(30));
+        assert_eq!("W110".parse::<Elementary>().unwrap().number(), 110);
+        assert_eq!("rule 90".parse::<Elementary>().unwrap().name(), "Rule 90");
+        assert_eq!(Elementary::new(30).to_string(), "W30");
+        assert!("256".parse::<Elementary>().is_err());
+        assert!("R30".parse::<Elementary>().is_err());
+        assert!("K3,R1,C2187".parse::<Totalistic>().is_err());
+        assert!("K3,R1,C2186".parse::<Totalistic>().is_ok());
+        assert!("K1,R1,C0".parse::<Totalistic>().is_err());
+        assert!("K2,R1".parse::<Totalistic>().is_err());
+        assert!("K2,X1,C3".parse::<Totalistic>().is_err());
+        assert!("K40,R40,C1".parse::<Totalistic>().is_ok());
+        assert!("K2,é1,C3".parse::<Totalistic>().is_err());
+        assert!("K2,R1,Cé".parse::<Totalistic>().is_err());
+        assert!("K2,R18446744073709551615,C3".parse::<Totalistic>().is_err());
+        assert!("K2,R2147483648,C1".parse::<Totalistic>().is_err());
+        assert!("K256,R1000,C1".parse::<Totalistic>().is_ok());
+    }
+}
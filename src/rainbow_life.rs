@@ -1,19 +1,40 @@
-use super::Square;
+use super::{Context, FiniteState, Neighborhood, Rule, Square, MOORE,
+            HEXAGONAL};
 
-use rand;
 use rand::Rng;
 
-use std::collections::HashMap;
-
 /// Enum for a game of life that is multicolored with more complex rules
-#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Default)]
 pub enum State {
     Red,
     Green,
     Blue,
+    #[default]
     Dead,
 }
 
+impl FiniteState for State {
+    fn count() -> usize { 4 }
+
+    fn index(&self) -> usize {
+        match *self {
+            State::Dead => 0,
+            State::Red => 1,
+            State::Green => 2,
+            State::Blue => 3,
+        }
+    }
+
+    fn from_index(index: usize) -> State {
+        match index {
+            0 => State::Dead,
+            1 => State::Red,
+            2 => State::Green,
+            _ => State::Blue,
+        }
+    }
+}
+
 impl State {
     fn enemy(&self) -> Self {
         use self::State::{Red, Blue, Green, Dead};
@@ -29,92 +50,179 @@ impl State {
 impl<'a> Square<'a, State>
 where State: 'a {
     /// Return the number of nodes alive surrounding this number
-    fn alive_count(&self) -> usize {
-        let this = self.get(0, 0);
-//         println!("{:?}", self.within_ortholinear(1));
-        self.within_ortholinear(1)
-            .iter()
-            .filter(|e| { **e != State::Dead && **e != this.enemy() })
-            .count()
+    fn alive_count(&self, hood: &Neighborhood) -> usize {
+        let enemy = self.get(0, 0).enemy();
+        self.count_neighbors(hood, |e| { *e != State::Dead && *e != enemy })
     }
 }
 
-impl Default for State {
-    fn default() -> State { State::Dead }
+/// Compute the next state of a square, breaking ties between colors with
+/// randomness from the thread's generator
+pub fn rule(curr: Square<State>) -> State {
+    rule_with(curr, &Context::random())
 }
 
-pub fn rule(curr: Square<State>) -> State {
+/// Compute the next state of a square, breaking ties between colors with
+/// randomness drawn from a context
+pub fn rule_with(curr: Square<State>, ctx: &Context) -> State {
+    evolve(curr, ctx, &MOORE, &[2, 3], 3)
+}
+
+/// Compute the next state of a hexagon on a hexagonal lattice, breaking ties
+/// between colors with randomness from the thread's generator. Hexagons
+/// survive with 3 or 4 alive neighbors and are born with 2 like in B2/S34H
+pub fn hex_rule(curr: Square<State>) -> State {
+    hex_rule_with(curr, &Context::random())
+}
+
+/// Compute the next state of a hexagon on a hexagonal lattice, breaking ties
+/// between colors with randomness drawn from a context
+pub fn hex_rule_with(curr: Square<State>, ctx: &Context) -> State {
+    evolve(curr, ctx, &HEXAGONAL, &[3, 4], 2)
+}
+
+/// Compute the next state of a square that survives with one of the given
+/// numbers of alive neighbors, not counting its enemies, and is born with the
+/// most frequent color of its neighbors when exactly birth of them are alive
+fn evolve(curr: Square<State>, ctx: &Context, hood: &Neighborhood,
+          survival: &[usize], birth: usize) -> State {
     use self::State::Dead;
 
     let curr_val = *curr.get(0, 0);
+    let count = curr.alive_count(hood);
 
     if curr_val != Dead {
-        match curr.alive_count() {
-            2 | 3 => curr_val,
-            _ => Dead,
-        }
+        if survival.contains(&count) { curr_val } else { Dead }
+    } else if count == birth {
+        let neighbors = curr.neighbors(hood).cloned()
+            .filter(|e| { *e != Dead })
+            .collect::<Vec<State>>();
+        let (x, y) = curr.coordinate();
+        mode(&neighbors[..], &mut ctx.rng(x, y))
     } else {
-        match curr.alive_count() {
-            3 => {
-                let neighbors = curr.within_ortholinear(1).iter().cloned()
-                    .filter(|e| { *e != Dead })
-                    .collect::<Vec<State>>();
-                // println!("{:?}", neighbors);
-                let m = mode(&neighbors[..]);
-
-                // println!("{:?}", m);
-                m
-            },
-            _ => Dead,
-        }
+        Dead
+    }
+}
+
+/// Multicolored Game of Life as a Rule, ties between colors are broken randomly
+#[derive(Clone, Copy, Debug, PartialEq, Default)]
+pub struct RainbowLife;
+
+impl Rule for RainbowLife {
+    type State = State;
+
+    fn name(&self) -> &str { "Multicolored Life" }
+    fn state_count(&self) -> Option<usize> { Some(4) }
+
+    fn is_deterministic(&self) -> bool { false }
+
+    fn step(&self, curr: Square<State>) -> State { rule(curr) }
+
+    fn step_with(&self, curr: Square<State>, ctx: &Context) -> State {
+        rule_with(curr, ctx)
     }
 }
 
-/// Return the most frequent thing in the slice. Break ties randomly or based on
-/// some other metric
-fn mode(data: &[State]) -> State {
-    use std::cmp::Ordering::{Less, Equal, Greater};
-    data.iter().cloned()
-        // use a map to keep counts of each
-        .fold(HashMap::new(),
-              |mut map, e| { *map.entry(e).or_insert(0) += 1; map })
-        .into_iter()
-        // probably want to switch this back to keeping track of a Vec
-        .fold(None, |acc, (s, n)| {
-            acc.map_or(Some((n, s, 1)), |(n_, s_, c)| {
-                match n.cmp(&n_) {
-                    Greater => Some((n, s, 1)),
-                    Equal   => Some((n_, s_, c + 1)),
-                    Less    => Some((n_, s_, c)),
-                }
-            })
-        })
-        .map_or(random_color(), |(_, s, c)| {
-            if c > 1 { random_color() } else { s }
-        })
+/// Multicolored Life on a hexagonal lattice as a Rule, ties between colors are
+/// broken randomly
+#[derive(Clone, Copy, Debug, PartialEq, Default)]
+pub struct HexRainbowLife;
+
+impl Rule for HexRainbowLife {
+    type State = State;
+
+    fn name(&self) -> &str { "Hexagonal Multicolored Life" }
+    fn neighborhood(&self) -> Neighborhood { HEXAGONAL }
+    fn state_count(&self) -> Option<usize> { Some(4) }
+
+    fn is_deterministic(&self) -> bool { false }
+
+    fn step(&self, curr: Square<State>) -> State { hex_rule(curr) }
+
+    fn step_with(&self, curr: Square<State>, ctx: &Context) -> State {
+        hex_rule_with(curr, ctx)
+    }
 }
 
-fn random_color() -> State {
+/// Return the most frequent color in the slice. If several colors are the most
+/// frequent, or there are none, any of the three colors is picked at random
+fn mode<R>(data: &[State], rng: &mut R) -> State
+where R: Rng {
     use self::State::{Red, Blue, Green};
-    // Unwrap is safe since there will always be a result of some kind
-    *rand::thread_rng().choose(&[Red, Blue, Green]).unwrap()
+    let mut counts = [0; 4];
+    for e in data.iter() {
+        counts[e.index()] += 1;
+    }
+    let colors = [Red, Blue, Green];
+    let most = colors.iter().map(|c| { counts[c.index()] }).max().unwrap_or(0);
+    let mut tied = colors.iter().filter(|c| { counts[c.index()] == most });
+    match (tied.next(), tied.next()) {
+        (Some(&color), None) => color,
+        // Unwrap is safe since there is always a color to choose
+        _ => *rng.choose(&colors).unwrap(),
+    }
 }
 
 #[cfg(test)]
 mod tests {
-    use super::super::Frame;
-    use super::rule;
+    use super::super::{Context, Frame, Simulation};
+    use rand::Rng;
+    use super::{rule, HexRainbowLife, RainbowLife};
     use super::State;
     use super::State::{Red, Blue, Green, Dead};
     use super::mode;
 
     #[test]
     fn mode_test() {
-        assert_eq!(mode(&[Red, Red, Red]), Red);
-        assert_eq!(mode(&[Red, Red, Blue]), Red);
-        assert_eq!(mode(&[Red, Red, Blue, Green]), Red);
-        assert_eq!(mode(&[Blue, Green, Red, Green, Blue, Red, Blue]), Blue);
-        assert!(mode(&[Red, Blue, Green]) != Dead);
+        let mut rng = Context::new(7).rng(0, 0);
+        assert_eq!(mode(&[Red, Red, Red], &mut rng), Red);
+        assert_eq!(mode(&[Red, Red, Blue], &mut rng), Red);
+        assert_eq!(mode(&[Red, Red, Blue, Green], &mut rng), Red);
+        assert_eq!(mode(&[Blue, Green, Red, Green, Blue, Red, Blue], &mut rng),
+                   Blue);
+        assert!(mode(&[Red, Blue, Green], &mut rng) != Dead);
+    }
+
+    #[test]
+    fn mode_ties() {
+        // any tie is broken between all three colors, even the ones that
+        // aren't among the most frequent, and every one of them gets picked
+        // for some seed
+        let picks = (0..64).map(|seed| {
+            mode(&[Red, Green, Red, Green, Blue], &mut Context::new(seed).rng(0, 0))
+        }).collect::<Vec<_>>();
+        assert!([Red, Green, Blue].iter().all(|c| { picks.contains(c) }));
+
+        // the same seed always breaks a tie the same way
+        let three = [Red, Blue, Green];
+        let ctx = Context::new(12).with_generation(3);
+        assert_eq!(mode(&three, &mut ctx.rng(4, 5)),
+                   mode(&three, &mut ctx.rng(4, 5)));
+        assert!(mode(&[], &mut ctx.rng(0, 0)) != Dead);
+    }
+
+    #[test]
+    fn seeded_runs_repeat() {
+        // a frame with many births between three different colors
+        let mut rng = Context::new(1).rng(0, 0);
+        let mut frame = Frame::<State>::new(24, 24);
+        for y in 0..24 {
+            for x in 0..24 {
+                *frame.get_mut(x, y) = *rng.choose(&[Red, Green, Blue, Dead])
+                    .unwrap();
+            }
+        }
+
+        let run = |seed: u64, threads: usize| {
+            let mut sim = Simulation::seeded(frame.clone(), seed);
+            for _ in 0..20 {
+                sim.advance_parallel(threads, &RainbowLife);
+            }
+            sim.into_frame()
+        };
+        assert_eq!(run(99, 1), run(99, 1));
+        assert_eq!(run(99, 1), run(99, 4));
+        assert!(run(99, 1) != run(100, 1));
     }
 
     #[test]
@@ -142,6 +250,43 @@ mod tests {
         assert_eq!(frame3, frame1);
     }
 
+    #[test]
+    fn as_rule() {
+        let mut frame = Frame::<State>::new(4, 4);
+        *frame.get_mut(1, 0) = Blue;
+        *frame.get_mut(1, 1) = Blue;
+        *frame.get_mut(1, 2) = Blue;
+
+        assert_eq!(frame.advance(&RainbowLife), frame.next_frame(rule));
+    }
+
+    #[test]
+    fn hexagonal() {
+        // two neighboring hexagons give birth to the two hexagons that
+        // neighbor both of them and die, and the same happens in turn
+        let mut frame = Frame::<State>::new(8, 8);
+        *frame.get_mut(3, 3) = Green;
+        *frame.get_mut(4, 3) = Green;
+
+        let ctx = Context::new(4);
+        let next = frame.advance_with(&HexRainbowLife, &ctx);
+        let mut expected = Frame::<State>::new(8, 8);
+        *expected.get_mut(4, 2) = Green;
+        *expected.get_mut(3, 4) = Green;
+        assert_eq!(next, expected);
+        assert_eq!(next.advance_with(&HexRainbowLife, &ctx), frame);
+
+        // a tie between two colors picks any of the three colors, and every
+        // one of them is picked for some seed
+        *frame.get_mut(4, 3) = Blue;
+        let births = (0..64).map(|seed| {
+            let next = frame.advance_with(&HexRainbowLife, &Context::new(seed));
+            assert_eq!(*next.get(3, 3), Dead);
+            *next.get(4, 2)
+        }).collect::<Vec<_>>();
+        assert!([Red, Green, Blue].iter().all(|c| { births.contains(c) }));
+    }
+
     #[test]
     fn glider_one_color() {
         // Create a glider as pictured and follow its transformations:
@@ -0,0 +1,375 @@
+use super::{Context, Frame, Neighborhood, Square, MOORE};
+
+use std::error::Error;
+use std::fmt;
+use std::str::FromStr;
+
+/// A rule that computes the next state of a square from its surroundings
+pub trait Rule {
+    /// The state of a single square
+    type State;
+
+    /// A human readable name for the rule
+    fn name(&self) -> &str;
+
+    /// The neighborhood that the rule looks at to compute the next state
+    fn neighborhood(&self) -> Neighborhood {
+        MOORE
+    }
+
+    /// The number of distinct states that the rule can produce, if it is
+    /// finite and known
+    fn state_count(&self) -> Option<usize> {
+        None
+    }
+
+    /// Whether the rule always computes the same state for the same
+    /// surroundings
+    fn is_deterministic(&self) -> bool {
+        true
+    }
+
+    /// Whether the rule computes the same state for the same surroundings at
+    /// every coordinate
+    fn is_uniform(&self) -> bool {
+        true
+    }
+
+    /// Whether the rule computes the same state for the same surroundings in
+    /// every generation. Rules that look at the generation or the statistics
+    /// in the context aren't
+    fn is_stationary(&self) -> bool {
+        true
+    }
+
+    /// Compute the next state of a square
+    fn step(&self, curr: Square<Self::State>) -> Self::State;
+
+    /// Compute the next state of a square, drawing any randomness it needs
+    /// from a context so that runs with the same seed are identical. Rules
+    /// that don't use randomness don't need to implement this
+    fn step_with(&self, curr: Square<Self::State>, ctx: &Context)
+        -> Self::State {
+        let _ = ctx;
+        self.step(curr)
+    }
+
+    /// Compute the whole next frame at once, if the rule has a faster way to
+    /// do so than stepping each square. Frames are advanced square by square
+    /// when this returns None, which it does by default
+    fn advance_frame(&self, frame: &Frame<Self::State>)
+        -> Option<Frame<Self::State>> {
+        let _ = frame;
+        None
+    }
+}
+
+impl<R> Rule for &R
+where R: Rule + ?Sized {
+    type State = R::State;
+
+    fn name(&self) -> &str { (**self).name() }
+    fn neighborhood(&self) -> Neighborhood { (**self).neighborhood() }
+    fn state_count(&self) -> Option<usize> { (**self).state_count() }
+    fn is_deterministic(&self) -> bool { (**self).is_deterministic() }
+    fn is_uniform(&self) -> bool { (**self).is_uniform() }
+    fn is_stationary(&self) -> bool { (**self).is_stationary() }
+
+    fn step(&self, curr: Square<Self::State>) -> Self::State {
+        (**self).step(curr)
+    }
+
+    fn step_with(&self, curr: Square<Self::State>, ctx: &Context)
+        -> Self::State {
+        (**self).step_with(curr, ctx)
+    }
+
+    fn advance_frame(&self, frame: &Frame<Self::State>)
+        -> Option<Frame<Self::State>> {
+        (**self).advance_frame(frame)
+    }
+}
+
+impl<R> Rule for Box<R>
+where R: Rule + ?Sized {
+    type State = R::State;
+
+    fn name(&self) -> &str { (**self).name() }
+    fn neighborhood(&self) -> Neighborhood { (**self).neighborhood() }
+    fn state_count(&self) -> Option<usize> { (**self).state_count() }
+    fn is_deterministic(&self) -> bool { (**self).is_deterministic() }
+    fn is_uniform(&self) -> bool { (**self).is_uniform() }
+    fn is_stationary(&self) -> bool { (**self).is_stationary() }
+
+    fn step(&self, curr: Square<Self::State>) -> Self::State {
+        (**self).step(curr)
+    }
+
+    fn step_with(&self, curr: Square<Self::State>, ctx: &Context)
+        -> Self::State {
+        (**self).step_with(curr, ctx)
+    }
+
+    fn advance_frame(&self, frame: &Frame<Self::State>)
+        -> Option<Frame<Self::State>> {
+        (**self).advance_frame(frame)
+    }
+}
+
+/// A type of state with finitely many values, each of which is numbered by an
+/// index from 0 up to the number of values
+pub trait FiniteState: Sized {
+    /// The number of values
+    fn count() -> usize;
+
+    /// The index of this value
+    fn index(&self) -> usize;
+
+    /// The value with an index, which must be less than the number of values
+    fn from_index(index: usize) -> Self;
+}
+
+impl<T> Frame<T>
+where T: Clone {
+    /// return the next frame of the simulation advancing each square with a
+    /// rule, or the whole frame at once if the rule can
+    pub fn advance<R>(&self, rule: &R) -> Frame<T>
+    where R: Rule<State = T> + ?Sized {
+        rule.advance_frame(self).unwrap_or_else(|| {
+            self.next_frame(|sq| { rule.step(sq) })
+        })
+    }
+
+    /// return the next frame of the simulation advancing each square with a
+    /// rule that draws its randomness from a context, or the whole frame at
+    /// once if the rule can
+    pub fn advance_with<R>(&self, rule: &R, ctx: &Context) -> Frame<T>
+    where R: Rule<State = T> + ?Sized {
+        rule.advance_frame(self).unwrap_or_else(|| {
+            self.next_frame(|sq| { rule.step_with(sq, ctx) })
+        })
+    }
+}
+
+impl<T> Frame<T>
+where T: Clone + Send + Sync {
+    /// return the next frame of the simulation advancing each square with a
+    /// rule, computing bands of rows on separate threads
+    pub fn advance_parallel<R>(&self, threads: usize, rule: &R) -> Frame<T>
+    where R: Rule<State = T> + Sync + ?Sized {
+        self.next_frame_parallel(threads, |sq| { rule.step(sq) })
+    }
+
+    /// return the next frame of the simulation advancing each square with a
+    /// rule that draws its randomness from a context, computing bands of rows
+    /// on separate threads. The result doesn't depend on the number of threads
+    pub fn advance_parallel_with<R>(&self, threads: usize, rule: &R,
+                                    ctx: &Context) -> Frame<T>
+    where R: Rule<State = T> + Sync + ?Sized {
+        self.next_frame_parallel(threads, |sq| { rule.step_with(sq, ctx) })
+    }
+}
+
+/// An error describing why a rulestring couldn't be parsed
+#[derive(Clone, Debug, PartialEq)]
+pub struct ParseRuleError {
+    rulestring: String,
+    reason: String,
+}
+
+impl ParseRuleError {
+    /// Create an error for a rulestring
+    pub fn new<S>(rulestring: &str, reason: S) -> ParseRuleError
+    where S: Into<String> {
+        ParseRuleError {
+            rulestring: rulestring.to_string(),
+            reason: reason.into(),
+        }
+    }
+
+    /// The rulestring that couldn't be parsed
+    pub fn rulestring(&self) -> &str {
+        &self.rulestring
+    }
+}
+
+impl fmt::Display for ParseRuleError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "invalid rulestring {:?}: {}", self.rulestring, self.reason)
+    }
+}
+
+impl Error for ParseRuleError {}
+
+/// Split a rulestring in either "B3/S23" or "23/3" notation into its birth
+/// and survival parts
+pub(crate) fn split_birth_survival(rulestring: &str)
+    -> Result<(&str, &str), ParseRuleError> {
+    let parts = rulestring.split('/').map(str::trim).collect::<Vec<_>>();
+    if parts.len() != 2 {
+        return Err(ParseRuleError::new(
+            rulestring, "expected a birth and a survival condition"));
+    }
+
+    let prefixed = |part: &str, prefix: char| {
+        part.chars().next().is_some_and(|c| { c.to_ascii_uppercase() == prefix })
+    };
+    let (a, b) = (parts[0], parts[1]);
+    if prefixed(a, 'B') && prefixed(b, 'S') {
+        Ok((&a[1..], &b[1..]))
+    } else if prefixed(a, 'S') && prefixed(b, 'B') {
+        Ok((&b[1..], &a[1..]))
+    } else if !a.starts_with(char::is_alphabetic)
+        && !b.starts_with(char::is_alphabetic) {
+        // the older notation lists survival before birth
+        Ok((b, a))
+    } else {
+        Err(ParseRuleError::new(
+            rulestring, "expected the conditions to start with B and S"))
+    }
+}
+
+/// Parse a list of digits like "236" into a bit set of neighbor counts, where
+/// bit n is set when the count n is in the list. Counts from 10 to 12 are
+/// written as the letters a to c
+pub(crate) fn parse_counts(rulestring: &str, digits: &str, max: usize)
+    -> Result<u32, ParseRuleError> {
+    let mut counts = 0;
+    for c in digits.chars() {
+        match c.to_digit(16) {
+            Some(n) if n as usize <= max => counts |= 1 << n,
+            _ => return Err(ParseRuleError::new(
+                rulestring,
+                format!("{:?} is not a neighbor count between 0 and {}",
+                        c, max))),
+        }
+    }
+    Ok(counts)
+}
+
+/// Turn a list of neighbor counts into a bit set, where bit n is set when the
+/// count n is in the list
+pub(crate) fn count_bits(counts: &[usize]) -> u32 {
+    counts.iter().fold(0, |acc, n| { acc | 1 << n })
+}
+
+/// Look up a rule from a catalog of names and rulestrings by its name, ignoring
+/// case. Returns the name as the catalog writes it along with the rule
+pub(crate) fn find_named<R>(catalog: &[(&'static str, &str)], name: &str)
+    -> Option<(&'static str, R)>
+where R: FromStr, R::Err: fmt::Debug {
+    catalog.iter()
+        .find(|&&(n, _)| { n.eq_ignore_ascii_case(name.trim()) })
+        .map(|&(n, rulestring)| {
+            let rule = rulestring.parse::<R>()
+                .expect("the catalog only contains valid rulestrings");
+            (n, rule)
+        })
+}
+
+/// Split a field of a comma separated rulestring like "R5" into its key, which
+/// is the first character in upper case, and the value after it
+pub(crate) fn split_field(field: &str) -> (Option<char>, &str) {
+    let mut chars = field.chars();
+    let key = chars.next().map(|c| { c.to_ascii_uppercase() });
+    (key, chars.as_str())
+}
+
+/// Write a bit set of neighbor counts as a list of digits
+pub(crate) fn format_counts(counts: u32) -> String {
+    (0..13).filter(|n| { counts & (1 << n) != 0 })
+        .filter_map(|n| { ::std::char::from_digit(n, 16) })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::{Frame, Square, Neighborhood};
+    use super::{Rule, split_birth_survival, parse_counts, format_counts};
+    use super::{count_bits, find_named};
+    use super::super::game_of_life::LifeLike;
+
+    /// Counts the number of neighbors to the left and right that are set
+    struct Horizontal;
+
+    impl Rule for Horizontal {
+        type State = u8;
+
+        fn name(&self) -> &str { "horizontal" }
+
+        fn neighborhood(&self) -> Neighborhood {
+            Neighborhood::Custom(vec![(-1, 0), (1, 0)])
+        }
+
+        fn step(&self, curr: Square<u8>) -> u8 {
+            curr.count_neighbors(&self.neighborhood(), |e| { *e > 0 }) as u8
+        }
+    }
+
+    #[test]
+    fn advance() {
+        let mut frame = Frame::<u8>::new_finite(3, 2);
+        *frame.get_mut(0, 0) = 1;
+        *frame.get_mut(2, 0) = 1;
+
+        let next = frame.advance(&Horizontal);
+
+        let mut expected = Frame::<u8>::new_finite(3, 2);
+        *expected.get_mut(1, 0) = 2;
+        assert_eq!(next, expected);
+    }
+
+    #[test]
+    fn advance_parallel() {
+        let mut frame = Frame::<u8>::new(5, 9);
+        *frame.get_mut(0, 0) = 1;
+        *frame.get_mut(2, 4) = 1;
+        *frame.get_mut(4, 8) = 1;
+        assert_eq!(frame.advance_parallel(3, &Horizontal),
+                   frame.advance(&Horizontal));
+    }
+
+    #[test]
+    fn trait_object() {
+        let rule: Box<dyn Rule<State = u8>> = Box::new(Horizontal);
+        assert_eq!(rule.name(), "horizontal");
+        assert_eq!(rule.neighborhood().len(), 2);
+        assert!(rule.is_deterministic());
+        assert_eq!(Frame::<u8>::new(2, 2).advance(&rule), Frame::new(2, 2));
+    }
+
+    #[test]
+    fn birth_survival() {
+        assert_eq!(split_birth_survival("B36/S23"), Ok(("36", "23")));
+        assert_eq!(split_birth_survival("s23/b36"), Ok(("36", "23")));
+        assert_eq!(split_birth_survival("23/36"), Ok(("36", "23")));
+        assert_eq!(split_birth_survival("B2/S"), Ok(("2", "")));
+        assert!(split_birth_survival("B3").is_err());
+        assert!(split_birth_survival("X3/S23").is_err());
+    }
+
+    #[test]
+    fn counts() {
+        assert_eq!(parse_counts("", "236", 8), Ok(0b1001100));
+        assert_eq!(parse_counts("", "", 8), Ok(0));
+        assert!(parse_counts("", "9", 8).is_err());
+        assert!(parse_counts("", "2a", 8).is_err());
+        assert_eq!(parse_counts("", "2ac", 12), Ok(0b1010000000100));
+        assert!(parse_counts("", "d", 12).is_err());
+        assert_eq!(format_counts(0b1001100), "236");
+        assert_eq!(format_counts(0b1010000000100), "2ac");
+    }
+    #[test]
+    fn shared_helpers() {
+        assert_eq!(count_bits(&[]), 0);
+        assert_eq!(count_bits(&[2, 3, 12]), 0b1_0000_0000_1100);
+        assert_eq!(format_counts(count_bits(&[0, 5, 11])), "05b");
+
+        let catalog = [("Life", "B3/S23"), ("HighLife", "B36/S23")];
+        let (name, rule) = find_named::<LifeLike>(&catalog, " highlife ")
+            .unwrap();
+        assert_eq!(name, "HighLife");
+        assert_eq!(rule, LifeLike::new(&[3, 6], &[2, 3]));
+        assert!(find_named::<LifeLike>(&catalog, "Seeds").is_none());
+    }
+}
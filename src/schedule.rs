@@ -0,0 +1,333 @@
+use super::{Context, Neighborhood, ParseRuleError, Rule, Square};
+
+use std::str::FromStr;
+
+/// A rule that changes over time. It is a list of phases that each advance a
+/// number of generations with their own rule, one after another, and starts
+/// over from the first phase once the last one is over unless the last phase
+/// lasts forever.
+///
+/// The rule of a generation is picked with the generation in the context, so a
+/// schedule advances a Simulation, or a frame with advance_with, with the rule
+/// of the current phase. Without a context there is no generation, so
+/// stepping a schedule with more than one phase that way panics rather than
+/// running its first phase forever. Schedules can't be compiled or advance a
+/// sparse grid, but they can be made of compiled rules
+#[derive(Clone, Debug, PartialEq)]
+pub struct Schedule<R> {
+    name: String,
+    phases: Vec<(R, u64)>,
+    /// the number of generations that the phases that don't last forever
+    /// last for in total
+    period: u64,
+    forever: bool,
+}
+
+impl<R> Schedule<R>
+where R: Rule {
+    /// Create a schedule without any phases, which need to be added before it
+    /// can advance anything
+    pub fn new() -> Schedule<R> {
+        Schedule {
+            name: String::new(),
+            phases: Vec::new(),
+            period: 0,
+            forever: false,
+        }
+    }
+
+    /// A schedule that always uses the same rule
+    pub fn constant(rule: R) -> Schedule<R> {
+        Schedule::new().then_forever(rule)
+    }
+
+    /// A schedule that uses each rule for one generation in turn
+    pub fn cycle(rules: Vec<R>) -> Schedule<R> {
+        rules.into_iter().fold(Schedule::new(), |s, r| { s.then(r, 1) })
+    }
+
+    /// A schedule that uses a on even generations and b on odd generations
+    pub fn alternate(a: R, b: R) -> Schedule<R> {
+        Schedule::cycle(vec![a, b])
+    }
+
+    /// A schedule that uses a for the given number of generations, then b
+    /// from then on
+    pub fn switch(a: R, generations: u64, b: R) -> Schedule<R> {
+        Schedule::new().then(a, generations).then_forever(b)
+    }
+
+    /// the schedule with a phase added that uses a rule for a number of
+    /// generations. Panics if the number is 0 or the phases last for more
+    /// than u64::MAX generations in total
+    pub fn then(mut self, rule: R, generations: u64) -> Schedule<R> {
+        assert!(!self.forever, "nothing can follow a phase that lasts forever");
+        assert!(generations > 0, "a phase must last at least one generation");
+        self.period = self.period.checked_add(generations)
+            .expect("the phases of a schedule last too many generations");
+        let name = if generations == 1 {
+            rule.name().to_string()
+        } else {
+            format!("{}*{}", rule.name(), generations)
+        };
+        self.push_name(&name);
+        self.phases.push((rule, generations));
+        self
+    }
+
+    /// the schedule with a last phase added that uses a rule forever
+    pub fn then_forever(mut self, rule: R) -> Schedule<R> {
+        assert!(!self.forever, "nothing can follow a phase that lasts forever");
+        let name = format!("{}*", rule.name());
+        self.push_name(&name);
+        self.phases.push((rule, 0));
+        self.forever = true;
+        self
+    }
+
+    /// Add the name of a phase to the name of the schedule
+    fn push_name(&mut self, name: &str) {
+        if !self.name.is_empty() {
+            self.name.push_str("; ");
+        }
+        self.name.push_str(name);
+    }
+
+    /// the rules of the phases with the number of generations they last for,
+    /// which is 0 for a last phase that lasts forever
+    pub fn phases(&self) -> &[(R, u64)] {
+        &self.phases
+    }
+
+    /// the rule that advances a generation
+    pub fn rule_at(&self, generation: u64) -> &R {
+        assert!(!self.phases.is_empty(), "the schedule has no phases");
+        let mut g = if self.forever {
+            generation
+        } else {
+            generation % self.period
+        };
+        for &(ref rule, n) in self.phases.iter() {
+            if g < n {
+                return rule;
+            }
+            g -= n;
+        }
+        &self.phases[self.phases.len() - 1].0
+    }
+}
+
+impl<R> Default for Schedule<R>
+where R: Rule {
+    fn default() -> Schedule<R> {
+        Schedule::new()
+    }
+}
+
+impl<R> Rule for Schedule<R>
+where R: Rule {
+    type State = R::State;
+
+    fn name(&self) -> &str { &self.name }
+
+    fn neighborhood(&self) -> Neighborhood {
+        Neighborhood::covering(self.phases.iter()
+                               .map(|p| { p.0.neighborhood() }))
+    }
+
+    fn state_count(&self) -> Option<usize> {
+        self.phases.iter()
+            .map(|p| { p.0.state_count() })
+            .try_fold(0, |acc, n| { Some(acc.max(n?)) })
+    }
+
+    fn is_deterministic(&self) -> bool {
+        self.phases.iter().all(|p| { p.0.is_deterministic() })
+    }
+
+    fn is_uniform(&self) -> bool {
+        self.phases.iter().all(|p| { p.0.is_uniform() })
+    }
+
+    fn is_stationary(&self) -> bool {
+        self.phases.len() == 1 && self.phases[0].0.is_stationary()
+    }
+
+    fn step(&self, curr: Square<R::State>) -> R::State {
+        assert!(self.phases.len() == 1,
+                "{} needs the generation from a context to pick a phase",
+                self.name);
+        self.rule_at(0).step(curr)
+    }
+
+    fn step_with(&self, curr: Square<R::State>, ctx: &Context) -> R::State {
+        self.rule_at(ctx.generation()).step_with(curr, ctx)
+    }
+}
+
+/// Parses schedules like "B3/S23; B36/S23" where the phases are separated by
+/// semicolons. A phase is a rulestring followed by "*n" if it lasts n
+/// generations rather than one, or by "*" if it is the last phase and lasts
+/// forever, so "B3/S23*100; B36/S23*" switches rules after 100 generations
+impl<R> FromStr for Schedule<R>
+where R: Rule + FromStr<Err = ParseRuleError> {
+    type Err = ParseRuleError;
+
+    fn from_str(s: &str) -> Result<Schedule<R>, ParseRuleError> {
+        let mut schedule = Schedule::new();
+        for phase in s.split(';').map(str::trim) {
+            if schedule.forever {
+                return Err(ParseRuleError::new(
+                    s, "nothing can follow a phase that lasts forever"));
+            }
+            let (rule, count) = match phase.rfind('*') {
+                Some(i) => (&phase[..i], Some(phase[i + 1..].trim())),
+                None => (phase, None),
+            };
+            let rule = rule.trim().parse::<R>()?;
+            schedule = match count {
+                None => schedule.then(rule, 1),
+                Some("") => schedule.then_forever(rule),
+                Some(n) => match n.parse::<u64>() {
+                    Ok(0) => return Err(ParseRuleError::new(
+                        s, "a phase must last at least one generation")),
+                    Ok(n) if schedule.period.checked_add(n).is_none() =>
+                        return Err(ParseRuleError::new(
+                            s, "the phases last too many generations")),
+                    Ok(n) => schedule.then(rule, n),
+                    Err(_) => return Err(ParseRuleError::new(
+                        s, format!("{:?} is not a number of generations", n))),
+                },
+            };
+        }
+        Ok(schedule)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::{Boundary, Context, Rule, Simulation, UpdateScheme};
+    use super::super::compiled::{CompileError, Compiled};
+    use super::super::frame::tests::scattered;
+    use super::super::game_of_life::{LifeLike, State};
+    use super::super::sparse::SparseGrid;
+    use super::Schedule;
+
+    /// Parse a life-like rulestring
+    fn life(rulestring: &str) -> LifeLike {
+        rulestring.parse().unwrap()
+    }
+
+    #[test]
+    fn rule_at() {
+        let (a, b, c) = (life("B3/S23"), life("B36/S23"), life("B2/S"));
+        let names = |s: &Schedule<LifeLike>| {
+            (0..8).map(|g| { s.rule_at(g).name().to_string() })
+                .collect::<Vec<_>>()
+                .join(" ")
+        };
+        assert_eq!(names(&Schedule::alternate(a.clone(), b.clone())),
+                   ["B3/S23 B36/S23"; 4].join(" "));
+        assert_eq!(names(&Schedule::switch(a.clone(), 3, c.clone())),
+                   "B3/S23 B3/S23 B3/S23 B2/S B2/S B2/S B2/S B2/S");
+        let phases = Schedule::new().then(a.clone(), 2).then(c.clone(), 1)
+            .then(b.clone(), 2);
+        assert_eq!(names(&phases),
+                   "B3/S23 B3/S23 B2/S B36/S23 B36/S23 B3/S23 B3/S23 B2/S");
+        assert_eq!(phases.name(), "B3/S23*2; B2/S; B36/S23*2");
+        assert!(!phases.is_stationary());
+        assert!(Schedule::constant(a).is_stationary());
+    }
+
+    #[test]
+    fn parse() {
+        let s = "B3/S23*100; B36/S23*".parse::<Schedule<LifeLike>>().unwrap();
+        assert_eq!(s, Schedule::switch(life("B3/S23"), 100, life("B36/S23")));
+        assert_eq!(s.name(), "B3/S23*100; B36/S23*");
+        let s = "B3/S23; 23/36".parse::<Schedule<LifeLike>>().unwrap();
+        assert_eq!(s, Schedule::alternate(life("B3/S23"), life("B36/S23")));
+        assert!("B3/S23*; B36/S23".parse::<Schedule<LifeLike>>().is_err());
+        assert!("B3/S23*x".parse::<Schedule<LifeLike>>().is_err());
+        assert!("B3/S23; B9".parse::<Schedule<LifeLike>>().is_err());
+        assert!("B3/S23*0; B36/S23".parse::<Schedule<LifeLike>>().is_err());
+        assert!("B3/S23*18446744073709551615; B36/S23*2"
+                .parse::<Schedule<LifeLike>>().is_err());
+        let s = "B3/S23*18446744073709551614; B36/S23"
+            .parse::<Schedule<LifeLike>>().unwrap();
+        assert_eq!(*s.rule_at(5), life("B3/S23"));
+        assert_eq!(*s.rule_at(u64::MAX - 1), life("B36/S23"));
+        assert_eq!(*s.rule_at(u64::MAX), life("B3/S23"));
+    }
+
+    #[test]
+    fn simulation() {
+        let (a, b) = (life("B3/S23"), life("B36/S23"));
+        let schedule = Schedule::alternate(a.clone(), b.clone());
+        let frame = scattered(24, 24, Boundary::Toroidal);
+
+        let mut expected = frame.clone();
+        for g in 0..10 {
+            expected = expected.advance(if g % 2 == 0 { &a } else { &b });
+        }
+        let mut sim = Simulation::new(frame.clone());
+        for _ in 0..10 {
+            sim.advance(&schedule);
+        }
+        assert_eq!(*sim.frame(), expected);
+
+        // schedules are always fully evaluated and work in any stepping loop
+        let mut tracked = Simulation::new(frame.clone());
+        let mut parallel = Simulation::new(frame.clone());
+        for _ in 0..10 {
+            tracked.advance_tracked(&schedule);
+            parallel.advance_parallel(3, &schedule);
+        }
+        assert_eq!(*tracked.frame(), expected);
+        assert_eq!(*parallel.frame(), expected);
+
+        // a schedule with one phase doesn't need a context
+        let constant = Schedule::constant(a.clone());
+        assert_eq!(frame.advance(&constant), frame.advance(&a));
+
+        let mut frame = frame;
+        let sweep = UpdateScheme::Sweep;
+        let mut sim = Simulation::new(frame.clone()).with_scheme(sweep.clone());
+        for g in 0..4 {
+            let ctx = Context::new(sim.seed()).with_generation(g);
+            frame.update(schedule.rule_at(g), &sweep, &ctx);
+            sim.advance(&schedule);
+        }
+        assert_eq!(*sim.frame(), frame);
+    }
+
+    #[test]
+    fn compiled() {
+        let (a, b) = (life("B3/S23"), life("B36/S23"));
+        let schedule = Schedule::alternate(a.clone(), b.clone());
+        assert_eq!(Compiled::new(&schedule).err(),
+                   Some(CompileError::Nonstationary));
+
+        let compiled = Schedule::alternate(Compiled::new(a).unwrap(),
+                                           Compiled::new(b).unwrap());
+        let frame = scattered(16, 16, Boundary::Toroidal);
+        for g in 0..4 {
+            let ctx = Context::new(0).with_generation(g);
+            assert_eq!(frame.advance_with(&compiled, &ctx),
+                       frame.advance_with(&schedule, &ctx));
+        }
+    }
+
+    #[test]
+    #[should_panic]
+    fn without_context() {
+        let schedule = Schedule::alternate(life("B3/S23"), life("B36/S23"));
+        scattered(8, 8, Boundary::Toroidal).advance(&schedule);
+    }
+
+    #[test]
+    #[should_panic]
+    fn sparse() {
+        let schedule = Schedule::alternate(life("B3/S23"), life("B36/S23"));
+        SparseGrid::<State>::new().advance(&schedule);
+    }
+}
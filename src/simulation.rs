@@ -0,0 +1,642 @@
+use super::{Boundary, Context, Frame, Rule, Square, Stats};
+use super::UpdateScheme;
+
+use std::any::type_name;
+use std::mem;
+
+/// The side length of the tiles that changes are tracked in
+const TILE: usize = 16;
+
+/// A running simulation that keeps two frames and swaps between them each
+/// generation, so that advancing it doesn't allocate
+#[derive(Clone, Debug, PartialEq)]
+pub struct Simulation<T> {
+    current: Frame<T>,
+    next: Frame<T>,
+    /// the context for advancing the current generation
+    context: Context,
+    /// the order that squares are updated in
+    scheme: UpdateScheme,
+    /// whether statistics about each generation are recorded in the context
+    recording: bool,
+    /// whether the frame was changed through frame_mut since its statistics
+    /// were computed
+    stale: bool,
+    /// which tiles changed in the last generation, None if that isn't known
+    tracker: Option<Tracker<T>>,
+}
+
+/// Records which tiles of a frame changed in the last generation, and which
+/// tiles depend on each other through the boundary condition
+#[derive(Clone, Debug, PartialEq)]
+struct Tracker<T> {
+    width: usize,
+    height: usize,
+    boundary: Boundary<T>,
+    radius: usize,
+    /// the type and name of the rule whose changes are tracked
+    rule: (&'static str, String),
+    /// the number of tiles in each row of tiles
+    columns: usize,
+    changed: Vec<bool>,
+    /// for each tile, the tiles with squares that see it by looking past the
+    /// edge of the frame
+    wrapped: Vec<Vec<usize>>,
+}
+
+impl<T> Tracker<T>
+where T: Clone + PartialEq {
+    /// Start tracking a frame advanced with a rule that looks at most radius
+    /// squares away, knowing nothing about what changed
+    fn new(frame: &Frame<T>, radius: usize, rule: (&'static str, String))
+        -> Tracker<T> {
+        let (width, height) = (frame.width(), frame.height());
+        let columns = width.div_ceil(TILE);
+        let tiles = columns * height.div_ceil(TILE);
+        let tile = |x: usize, y: usize| { (y / TILE) * columns + x / TILE };
+
+        // only squares within radius of an edge can look past it
+        let mut wrapped = vec![Vec::new(); tiles];
+        let r = radius as isize;
+        for y in 0..height {
+            for x in 0..width {
+                if x >= radius && y >= radius && x + radius < width
+                    && y + radius < height {
+                    continue;
+                }
+                for j in -r..r + 1 {
+                    for i in -r..r + 1 {
+                        let (u, v) = (x as isize + i, y as isize + j);
+                        if u >= 0 && v >= 0 && (u as usize) < width
+                            && (v as usize) < height {
+                            continue;
+                        }
+                        if let Some((u, v)) = frame.resolve(u, v) {
+                            wrapped[tile(u, v)].push(tile(x, y));
+                        }
+                    }
+                }
+            }
+        }
+        for dependents in wrapped.iter_mut() {
+            dependents.sort();
+            dependents.dedup();
+        }
+
+        Tracker {
+            width,
+            height,
+            boundary: frame.boundary().clone(),
+            radius,
+            rule,
+            columns,
+            changed: vec![true; tiles],
+            wrapped,
+        }
+    }
+
+    /// Whether the tracker was built for a frame of this shape and boundary
+    /// condition and the same rule with this radius. Squares that didn't
+    /// change under one rule may well change under another
+    fn fits(&self, frame: &Frame<T>, radius: usize,
+            rule: &(&'static str, String)) -> bool {
+        self.width == frame.width() && self.height == frame.height()
+            && self.boundary == *frame.boundary() && self.radius == radius
+            && self.rule == *rule
+    }
+
+    /// The tiles that may change in the next generation, those that can see a
+    /// square that changed in the last one
+    fn active(&self) -> Vec<bool> {
+        let columns = self.columns as isize;
+        let rows = (self.changed.len() / self.columns.max(1)) as isize;
+        let reach = self.radius.div_ceil(TILE) as isize;
+        let mut active = vec![false; self.changed.len()];
+        for (t, _) in self.changed.iter().enumerate().filter(|&(_, &c)| { c }) {
+            let (tx, ty) = ((t % self.columns) as isize,
+                            (t / self.columns) as isize);
+            for v in (ty - reach).max(0)..(ty + reach + 1).min(rows) {
+                for u in (tx - reach).max(0)..(tx + reach + 1).min(columns) {
+                    active[(v * columns + u) as usize] = true;
+                }
+            }
+            for &d in self.wrapped[t].iter() {
+                active[d] = true;
+            }
+        }
+        active
+    }
+}
+
+impl<T> Simulation<T>
+where T: Clone + Default + PartialEq {
+    /// Start a simulation from a frame with a random seed
+    pub fn new(frame: Frame<T>) -> Simulation<T> {
+        Simulation::with_context(frame, Context::random())
+    }
+
+    /// Start a simulation from a frame with a seed. Simulations with the same
+    /// seed, frame and rule advance identically
+    pub fn seeded(frame: Frame<T>, seed: u64) -> Simulation<T> {
+        Simulation::with_context(frame, Context::new(seed))
+    }
+
+    /// Start a simulation from a frame with a context, which gives the seed,
+    /// the generation to start counting from and the parameters
+    pub fn with_context(frame: Frame<T>, context: Context) -> Simulation<T> {
+        Simulation {
+            next: frame.clone(),
+            current: frame,
+            context,
+            scheme: UpdateScheme::Synchronous,
+            recording: false,
+            stale: false,
+            tracker: None,
+        }
+    }
+
+    /// the simulation recording statistics about each generation in the
+    /// context, which costs an extra pass over the frame every generation.
+    /// Otherwise the statistics in the context are left empty
+    pub fn recording_stats(mut self) -> Simulation<T> {
+        self.recording = true;
+        self.stale = true;
+        self.refresh();
+        self
+    }
+
+    /// the simulation updating squares in the order given by an update scheme
+    pub fn with_scheme(mut self, scheme: UpdateScheme) -> Simulation<T> {
+        self.scheme = scheme;
+        self
+    }
+
+    /// the order that squares are updated in
+    pub fn scheme(&self) -> &UpdateScheme {
+        &self.scheme
+    }
+
+    /// change the order that squares are updated in
+    pub fn set_scheme(&mut self, scheme: UpdateScheme) {
+        self.scheme = scheme;
+    }
+
+    /// the current frame of the simulation
+    pub fn frame(&self) -> &Frame<T> {
+        &self.current
+    }
+
+    /// a mutable reference to the current frame of the simulation. Changes
+    /// made through it aren't counted as changed squares in the statistics
+    pub fn frame_mut(&mut self) -> &mut Frame<T> {
+        self.tracker = None;
+        self.stale = true;
+        &mut self.current
+    }
+
+    /// the number of generations the simulation has been advanced
+    pub fn generation(&self) -> u64 {
+        self.context.generation()
+    }
+
+    /// the seed that rules draw their randomness from
+    pub fn seed(&self) -> u64 {
+        self.context.seed()
+    }
+
+    /// the context for advancing the current generation. If the frame was
+    /// changed through frame_mut its statistics are only brought up to date
+    /// when the simulation is advanced
+    pub fn context(&self) -> &Context {
+        &self.context
+    }
+
+    /// Compute the statistics of the frame again if it was changed through
+    /// frame_mut
+    fn refresh(&mut self) {
+        if self.recording && self.stale {
+            let stats = self.current.stats();
+            self.context = self.context.clone().with_stats(stats);
+            self.stale = false;
+        }
+    }
+
+    /// set the value of a parameter that rules can see in the context. This
+    /// starts tracking changes over, since rules may depend on it
+    pub fn set_param(&mut self, name: &str, value: f64) {
+        self.context.set_param(name, value);
+        self.tracker = None;
+    }
+
+    /// Advance the simulation one generation using a step function
+    pub fn step<F>(&mut self, step: F)
+    where F: Fn(Square<T>) -> T {
+        self.current.step_into(&mut self.next, step);
+        self.swap();
+    }
+
+    /// Advance the simulation one generation with a rule, in the order given
+    /// by the update scheme
+    pub fn advance<R>(&mut self, rule: &R)
+    where R: Rule<State = T> + ?Sized {
+        self.refresh();
+        let ctx = &self.context;
+        if self.scheme != UpdateScheme::Synchronous {
+            self.next.clone_from(&self.current);
+            self.next.update(rule, &self.scheme, ctx);
+        } else if let Some(next) = rule.advance_frame(&self.current) {
+            self.next = next;
+        } else {
+            self.current.step_into(&mut self.next,
+                                   |sq| { rule.step_with(sq, ctx) });
+        }
+        self.swap();
+    }
+
+    /// Make the next frame the current one, recording what changed
+    fn swap(&mut self) {
+        let stats = if self.recording {
+            self.next.stats_since(&self.current)
+        } else {
+            Stats::default()
+        };
+        self.finish(stats);
+    }
+
+    /// Make the next frame, whose statistics are given, the current one
+    fn finish(&mut self, stats: Stats) {
+        self.tracker = None;
+        self.stale = false;
+        mem::swap(&mut self.current, &mut self.next);
+        self.context.advance(stats);
+    }
+
+    /// Stop the simulation and return its current frame
+    pub fn into_frame(self) -> Frame<T> {
+        self.current
+    }
+
+    /// Advance the simulation one generation with a rule like advance, but
+    /// only recompute the squares that can see a square that changed in the
+    /// last generation. The others keep their value, which gives the same
+    /// result as long as the rule is deterministic and only looks at squares
+    /// within its neighborhood, not at the generation or statistics in the
+    /// context. Nondeterministic or nonstationary rules and update schemes
+    /// other than the synchronous one are always fully evaluated. Rules may
+    /// read parameters, since setting one starts tracking over.
+    ///
+    /// Rules are told apart by their type and name, so passing a rule with a
+    /// different type or name than in the last generation starts tracking
+    /// over. A rule that changes without changing its name, such as one that
+    /// is changed in place, needs to be passed to advance once instead
+    pub fn advance_tracked<R>(&mut self, rule: &R)
+    where R: Rule<State = T> + ?Sized {
+        if !rule.is_deterministic() || !rule.is_stationary()
+            || self.scheme != UpdateScheme::Synchronous {
+            self.advance(rule);
+            return;
+        }
+        let radius = rule.neighborhood().radius();
+        let key = (type_name::<R>(), rule.name().to_string());
+        let mut tracker = match self.tracker.take() {
+            Some(tracker) if tracker.fits(&self.current, radius, &key) => {
+                tracker
+            },
+            _ => Tracker::new(&self.current, radius, key),
+        };
+        self.refresh();
+        let ctx = &self.context;
+
+        let stats = if tracker.changed.iter().all(|&c| { c }) {
+            // nothing is known so evaluate everything, which also gives the
+            // next frame the shape of the current one
+            self.current.step_into(&mut self.next,
+                                   |sq| { rule.step_with(sq, ctx) });
+            let columns = tracker.columns;
+            let mut changed = vec![false; tracker.changed.len()];
+            for (x, y, value) in self.current.enumerate_squares() {
+                if value != self.next.get(x, y) {
+                    changed[(y / TILE) * columns + x / TILE] = true;
+                }
+            }
+            tracker.changed = changed;
+            if self.recording {
+                self.next.stats_since(&self.current)
+            } else {
+                Stats::default()
+            }
+        } else {
+            // squares in inactive tiles didn't change in the last generation,
+            // so the next frame, which holds the generation before the current
+            // one, already has their values
+            let active = tracker.active();
+            let (width, height) = (self.current.width(), self.current.height());
+            let (recording, default) = (self.recording, T::default());
+            let mut population = ctx.stats().population();
+            let mut count = 0;
+            for (t, changed) in tracker.changed.iter_mut().enumerate() {
+                *changed = false;
+                if !active[t] {
+                    continue;
+                }
+                let (x0, y0) = ((t % tracker.columns) * TILE,
+                                (t / tracker.columns) * TILE);
+                for y in y0..(y0 + TILE).min(height) {
+                    for x in x0..(x0 + TILE).min(width) {
+                        let value = self.current.fixed(x, y).unwrap_or_else(|| {
+                            rule.step_with(self.current.square(x, y), ctx)
+                        });
+                        let old = self.current.get(x, y);
+                        if value != *old {
+                            *changed = true;
+                            count += 1;
+                            if recording {
+                                population += (value != default) as usize;
+                                population -= (*old != default) as usize;
+                            }
+                        }
+                        *self.next.get_mut(x, y) = value;
+                    }
+                }
+            }
+            if recording {
+                Stats::new(width * height, population, count)
+            } else {
+                Stats::default()
+            }
+        };
+
+        self.finish(stats);
+        self.tracker = Some(tracker);
+    }
+}
+
+impl<T> Simulation<T>
+where T: Clone + Default + PartialEq + Send + Sync {
+    /// Advance the simulation one generation using a step function, computing
+    /// bands of rows on separate threads
+    pub fn step_parallel<F>(&mut self, threads: usize, step: F)
+    where F: Fn(Square<T>) -> T + Sync {
+        self.current.step_into_parallel(&mut self.next, threads, step);
+        self.swap();
+    }
+
+    /// Advance the simulation one generation with a rule, computing bands of
+    /// rows on separate threads. Update schemes other than the synchronous one
+    /// update squares one after another, so they are computed on one thread
+    pub fn advance_parallel<R>(&mut self, threads: usize, rule: &R)
+    where R: Rule<State = T> + Sync + ?Sized {
+        if self.scheme != UpdateScheme::Synchronous {
+            self.advance(rule);
+            return;
+        }
+        self.refresh();
+        let ctx = &self.context;
+        self.current.step_into_parallel(&mut self.next, threads,
+                                        |sq| { rule.step_with(sq, ctx) });
+        self.swap();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::{Boundary, Context, Frame, Mask, Rule, Square, Stats};
+    use super::super::frame::tests::scattered;
+    use super::super::game_of_life::{GameOfLife, LifeLike, State};
+    use super::super::game_of_life::State::{Alive, Dead};
+    use super::super::generations::{self, Generations};
+    use super::super::larger_than_life::LargerThanLife;
+    use super::Simulation;
+
+    /// A frame with an irregular pattern of alive squares in a block in its
+    /// top left corner and still lifes everywhere else
+    fn mostly_still(w: usize, h: usize, boundary: Boundary<State>)
+        -> Frame<State> {
+        let soup = scattered(20, 20, Boundary::Toroidal);
+        let mut frame = Frame::<State>::new(w, h).with_boundary(boundary);
+        for y in 0..h {
+            for x in 0..w {
+                let block = x % 8 < 2 && y % 8 < 2 && (x > 24 || y > 24);
+                let noise = x < 20 && y < 20 && *soup.get(x, y) == Alive;
+                if block || noise {
+                    *frame.get_mut(x, y) = Alive;
+                }
+            }
+        }
+        frame
+    }
+
+    /// Check that tracked and full evaluation give the same frames
+    fn check_tracked<R>(frame: Frame<R::State>, rule: &R, generations: usize)
+    where R: Rule,
+          R::State: Clone + Default + PartialEq + ::std::fmt::Debug {
+        let mut sim = Simulation::new(frame.clone()).recording_stats();
+        let mut expected = frame;
+        for _ in 0..generations {
+            sim.advance_tracked(rule);
+            let next = expected.advance(rule);
+            assert_eq!(*sim.frame(), next);
+            assert_eq!(*sim.context().stats(), next.stats_since(&expected));
+            expected = next;
+        }
+    }
+
+    #[test]
+    fn matches_next_frame() {
+        let mut frame = Frame::<State>::new(8, 8);
+        *frame.get_mut(1, 0) = Alive;
+        *frame.get_mut(2, 1) = Alive;
+        *frame.get_mut(0, 2) = Alive;
+        *frame.get_mut(1, 2) = Alive;
+        *frame.get_mut(2, 2) = Alive;
+
+        let mut sim = Simulation::new(frame.clone());
+        for n in 0..12 {
+            if n % 2 == 0 {
+                sim.advance(&GameOfLife);
+            } else {
+                sim.advance_parallel(3, &GameOfLife);
+            }
+            frame = frame.advance(&GameOfLife);
+            assert_eq!(*sim.frame(), frame);
+        }
+        assert_eq!(sim.generation(), 12);
+        assert_eq!(sim.into_frame(), frame);
+    }
+
+    #[test]
+    fn reuses_buffers() {
+        let mut sim = Simulation::new(Frame::<u32>::new(4, 4));
+        sim.step(|sq| { sq.get(0, 0) + 1 });
+        let first = sim.frame().get(0, 0) as *const u32;
+        sim.step(|sq| { sq.get(0, 0) + 1 });
+        sim.step(|sq| { sq.get(0, 0) + 1 });
+        assert_eq!(sim.frame().get(0, 0) as *const u32, first);
+        assert_eq!(*sim.frame().get(3, 3), 3);
+    }
+
+    /// Squares come alive while the population is below the target parameter,
+    /// one row per generation, and die once it has been reached
+    struct Target;
+
+    impl Rule for Target {
+        type State = State;
+
+        fn name(&self) -> &str { "target" }
+
+        fn is_uniform(&self) -> bool { false }
+        fn is_stationary(&self) -> bool { false }
+
+        fn step(&self, curr: Square<State>) -> State {
+            self.step_with(curr, &Context::default())
+        }
+
+        fn step_with(&self, curr: Square<State>, ctx: &Context) -> State {
+            let target = ctx.param("target").unwrap_or(0.0) as usize;
+            let (_, y) = curr.coordinate();
+            if ctx.stats().population() >= target {
+                Dead
+            } else if y as u64 == ctx.generation() {
+                Alive
+            } else {
+                *curr.get(0, 0)
+            }
+        }
+    }
+
+    #[test]
+    fn context() {
+        let mut sim = Simulation::seeded(Frame::new(3, 5), 4)
+            .recording_stats();
+        sim.set_param("target", 7.0);
+        assert_eq!(*sim.context().stats(), Stats::new(15, 0, 0));
+        let mut populations = Vec::new();
+        for _ in 0..4 {
+            sim.advance(&Target);
+            populations.push(sim.context().stats().population());
+        }
+        assert_eq!(populations, vec![3, 6, 9, 0]);
+        assert_eq!(*sim.context().stats(), Stats::new(15, 0, 9));
+        assert_eq!(sim.context().generation(), 4);
+        assert_eq!(sim.seed(), 4);
+
+        // changes made by hand are counted before the next generation
+        *sim.frame_mut().get_mut(0, 4) = Alive;
+        sim.advance(&Target);
+        assert_eq!(sim.context().stats().population(), 3);
+        assert_eq!(sim.frame().stats().population(), 3);
+    }
+
+    #[test]
+    fn tracked_matches_full() {
+        let boundaries = [
+            Boundary::Toroidal,
+            Boundary::Constant(Dead),
+            Boundary::Constant(Alive),
+            Boundary::Reflecting,
+            Boundary::KleinBottle,
+            Boundary::Helical(5),
+        ];
+        for boundary in boundaries.iter() {
+            check_tracked(mostly_still(50, 41, boundary.clone()), &GameOfLife,
+                          40);
+            for &(w, h) in [(0, 0), (0, 5), (5, 0)].iter() {
+                let empty = Frame::new(w, h).with_boundary(boundary.clone());
+                check_tracked(empty, &GameOfLife, 2);
+            }
+        }
+        let b0 = "B0123478/S34678".parse::<LifeLike>().unwrap();
+        check_tracked(mostly_still(40, 40, Boundary::Toroidal), &b0, 10);
+
+        // a wall through the noise and a row of emitters along an edge
+        let mut frame = mostly_still(40, 40, Boundary::Toroidal);
+        frame.mask_rect(10, 0, 1, 30, Mask::Wall);
+        frame.mask_rect(0, 39, 40, 1, Mask::Emitter(Alive));
+        check_tracked(frame, &GameOfLife, 30);
+    }
+
+    #[test]
+    fn tracked_other_rules() {
+        let brain = Generations::named("Brian's Brain").unwrap();
+        let mut frame = Frame::new(48, 48);
+        for &(x, y) in [(3, 3), (4, 3), (40, 44), (40, 45)].iter() {
+            *frame.get_mut(x, y) = generations::State::Alive;
+        }
+        check_tracked(frame, &brain, 30);
+
+        let bosco = LargerThanLife::named("Bosco's Rule").unwrap();
+        let frame = mostly_still(70, 70, Boundary::Toroidal);
+        check_tracked(frame, &bosco, 5);
+    }
+
+    #[test]
+    fn tracked_after_changes() {
+        let mut sim = Simulation::new(mostly_still(40, 40, Boundary::Toroidal));
+        for _ in 0..30 {
+            sim.advance_tracked(&GameOfLife);
+        }
+        *sim.frame_mut().get_mut(33, 33) = Alive;
+        sim.advance(&GameOfLife);
+        sim.advance_tracked(&GameOfLife);
+        *sim.frame_mut() = mostly_still(30, 20, Boundary::Reflecting);
+        let mut expected = sim.frame().clone();
+        for _ in 0..10 {
+            sim.advance_tracked(&GameOfLife);
+            expected = expected.advance(&GameOfLife);
+        }
+        assert_eq!(*sim.frame(), expected);
+    }
+
+    #[test]
+    fn tracked_switching_rules() {
+        // a block is still under Life, so every tile is unchanged before the
+        // rule switches to one where it grows
+        let mut frame = Frame::new(16, 16);
+        for &(x, y) in [(7, 7), (8, 7), (7, 8), (8, 8)].iter() {
+            *frame.get_mut(x, y) = Alive;
+        }
+        let life = "B3/S23".parse::<LifeLike>().unwrap();
+        let b2 = "B2/S23".parse::<LifeLike>().unwrap();
+        let mut sim = Simulation::new(frame.clone());
+        sim.advance_tracked(&life);
+        sim.advance_tracked(&life);
+        sim.advance_tracked(&b2);
+        sim.advance_tracked(&b2);
+        assert_eq!(*sim.frame(),
+                   frame.advance(&life).advance(&life).advance(&b2)
+                       .advance(&b2));
+    }
+
+    /// Every square comes alive once the parameter "on" is set
+    struct Switch;
+
+    impl Rule for Switch {
+        type State = State;
+
+        fn name(&self) -> &str { "switch" }
+
+        fn step(&self, curr: Square<State>) -> State {
+            self.step_with(curr, &Context::default())
+        }
+
+        fn step_with(&self, curr: Square<State>, ctx: &Context) -> State {
+            if ctx.param("on").unwrap_or(0.0) > 0.0 {
+                Alive
+            } else {
+                *curr.get(0, 0)
+            }
+        }
+    }
+
+    #[test]
+    fn tracked_params() {
+        // nothing changes until the parameter is set, after which every tile
+        // has to be evaluated again
+        let mut sim = Simulation::new(Frame::new(40, 40));
+        sim.advance_tracked(&Switch);
+        sim.advance_tracked(&Switch);
+        sim.set_param("on", 1.0);
+        sim.advance_tracked(&Switch);
+        assert_eq!(sim.frame().stats().population(), 1600);
+    }
+}
@@ -0,0 +1,289 @@
+use super::{Boundary, Frame, Rule, Square};
+
+use std::collections::HashMap;
+
+/// The side length of the tiles that a sparse grid is stored in
+const TILE: usize = 16;
+
+/// A TILE by TILE block of a sparse grid
+#[derive(Clone, Debug, PartialEq)]
+struct Tile<T> {
+    cells: Vec<T>,
+    /// the number of cells that don't have the default value
+    population: usize,
+}
+
+/// An unbounded grid that only stores the tiles that contain cells that don't
+/// have the default value. The default value must be quiescent: a cell with the
+/// default value surrounded by cells with the default value must keep it,
+/// otherwise the grid would fill the whole plane
+#[derive(Clone, Debug, PartialEq)]
+pub struct SparseGrid<T> {
+    tiles: HashMap<(i64, i64), Tile<T>>,
+    default: T,
+}
+
+/// The tile containing a coordinate and the index of the coordinate in it
+fn locate(x: i64, y: i64) -> ((i64, i64), usize) {
+    let t = TILE as i64;
+    let index = y.rem_euclid(t) as usize * TILE + x.rem_euclid(t) as usize;
+    ((x.div_euclid(t), y.div_euclid(t)), index)
+}
+
+impl<T> SparseGrid<T>
+where T: Default + Clone + PartialEq {
+    /// Creates an empty grid
+    pub fn new() -> SparseGrid<T> {
+        SparseGrid {
+            tiles: HashMap::new(),
+            default: T::default(),
+        }
+    }
+
+    /// Creates a grid with the cells of a frame with its top left corner at
+    /// (0, 0). The frame's boundary condition is ignored
+    pub fn from_frame(frame: &Frame<T>) -> SparseGrid<T> {
+        let mut grid = SparseGrid::new();
+        for (x, y, value) in frame.enumerate_squares() {
+            grid.set(x as i64, y as i64, value.clone());
+        }
+        grid
+    }
+
+    /// the data at (x, y)
+    pub fn get(&self, x: i64, y: i64) -> &T {
+        let (tile, index) = locate(x, y);
+        self.tiles.get(&tile).map_or(&self.default, |t| { &t.cells[index] })
+    }
+
+    /// change the data at (x, y)
+    pub fn set(&mut self, x: i64, y: i64, value: T) {
+        let (key, index) = locate(x, y);
+        let is_default = value == self.default;
+        if is_default && !self.tiles.contains_key(&key) {
+            return;
+        }
+
+        let default = &self.default;
+        let tile = self.tiles.entry(key).or_insert_with(|| {
+            Tile { cells: vec![default.clone(); TILE * TILE], population: 0 }
+        });
+        let was_default = tile.cells[index] == *default;
+        tile.cells[index] = value;
+        match (was_default, is_default) {
+            (true, false) => tile.population += 1,
+            (false, true) => tile.population -= 1,
+            _ => {},
+        }
+        if tile.population == 0 {
+            self.tiles.remove(&key);
+        }
+    }
+
+    /// the number of cells that don't have the default value
+    pub fn population(&self) -> usize {
+        self.tiles.values().map(|t| { t.population }).sum()
+    }
+
+    /// Iterate over the coordinates and values of the cells that don't have the
+    /// default value, in no particular order
+    pub fn iter(&self) -> impl Iterator<Item = (i64, i64, &T)> + '_ {
+        self.tiles.iter().flat_map(move |(&(tx, ty), tile)| {
+            tile.cells.iter().enumerate()
+                .filter(move |&(_, value)| { *value != self.default })
+                .map(move |(i, value)| {
+                    let x = tx * TILE as i64 + (i % TILE) as i64;
+                    let y = ty * TILE as i64 + (i / TILE) as i64;
+                    (x, y, value)
+                })
+        })
+    }
+
+    /// The smallest rectangle containing every cell that doesn't have the
+    /// default value, as the coordinates of its top left corner and its width
+    /// and height. None if every cell has the default value
+    pub fn bounding_box(&self) -> Option<(i64, i64, usize, usize)> {
+        self.iter().fold(None, |bounds, (x, y, _)| {
+            Some(match bounds {
+                None => (x, y, x, y),
+                Some((x0, y0, x1, y1)) =>
+                    (x.min(x0), y.min(y0), x.max(x1), y.max(y1)),
+            })
+        }).map(|(x0, y0, x1, y1)| {
+            (x0, y0, (x1 - x0 + 1) as usize, (y1 - y0 + 1) as usize)
+        })
+    }
+
+    /// The cells of a rectangle with its top left corner at (x, y) as a frame
+    /// where everything outside of it has the default value
+    pub fn to_frame(&self, x: i64, y: i64, width: usize, height: usize)
+        -> Frame<T> {
+        let mut frame = Frame::new_finite(width, height);
+        for j in 0..height {
+            for i in 0..width {
+                *frame.get_mut(i, j) = self.get(x + i as i64, y + j as i64).clone();
+            }
+        }
+        frame
+    }
+
+    /// The cells inside of the bounding box as a frame
+    pub fn bounding_frame(&self) -> Frame<T> {
+        match self.bounding_box() {
+            Some((x, y, w, h)) => self.to_frame(x, y, w, h),
+            None => Frame::new_finite(0, 0),
+        }
+    }
+
+    /// return the next grid of the simulation advancing it using a step
+    /// function that looks at most radius cells away from the cell it computes
+    pub fn next_frame<F>(&self, radius: usize, step: F) -> SparseGrid<T>
+    where F: Fn(Square<T>) -> T {
+        // only tiles within reach of a tile with cells that aren't the default
+        // value can change
+        let reach = radius.div_ceil(TILE) as i64;
+        let mut active = self.tiles.keys()
+            .flat_map(|&(tx, ty)| {
+                (-reach..reach + 1).flat_map(move |dy| {
+                    (-reach..reach + 1).map(move |dx| { (tx + dx, ty + dy) })
+                })
+            })
+            .collect::<Vec<_>>();
+        active.sort();
+        active.dedup();
+
+        // each tile is computed from a frame holding the tile and a margin of
+        // radius cells around it
+        let side = TILE + 2 * radius;
+        let mut local = Frame::new(side, side)
+            .with_boundary(Boundary::Constant(self.default.clone()));
+        let mut next = SparseGrid::new();
+        for (tx, ty) in active {
+            let (x0, y0) = (tx * TILE as i64 - radius as i64,
+                            ty * TILE as i64 - radius as i64);
+            for j in 0..side {
+                for i in 0..side {
+                    *local.get_mut(i, j) =
+                        self.get(x0 + i as i64, y0 + j as i64).clone();
+                }
+            }
+            for j in 0..TILE {
+                for i in 0..TILE {
+                    let value = step(local.square(i + radius, j + radius));
+                    next.set(x0 + (i + radius) as i64, y0 + (j + radius) as i64,
+                             value);
+                }
+            }
+        }
+        next
+    }
+
+    /// return the next grid of the simulation advancing each cell with a rule,
+    /// which must compute the same state for the same surroundings at every
+    /// coordinate and in every generation
+    pub fn advance<R>(&self, rule: &R) -> SparseGrid<T>
+    where R: Rule<State = T> + ?Sized {
+        assert!(rule.is_uniform(),
+                "{} depends on the coordinate, which a sparse grid can't track",
+                rule.name());
+        assert!(rule.is_stationary(),
+                "{} depends on the generation, which a sparse grid can't track",
+                rule.name());
+        self.next_frame(rule.neighborhood().radius(), |sq| { rule.step(sq) })
+    }
+}
+
+impl<T> Default for SparseGrid<T>
+where T: Default + Clone + PartialEq {
+    fn default() -> SparseGrid<T> {
+        SparseGrid::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::{Boundary, Frame};
+    use super::super::frame::tests::scattered;
+    use super::super::game_of_life::{GameOfLife, LifeLike, State};
+    use super::super::game_of_life::State::Alive;
+    use super::super::generations::{self, Generations};
+    use super::SparseGrid;
+
+    /// A glider heading south east with its top left corner at (x, y)
+    fn glider(grid: &mut SparseGrid<State>, x: i64, y: i64) {
+        grid.set(x + 1, y, Alive);
+        grid.set(x + 2, y + 1, Alive);
+        grid.set(x, y + 2, Alive);
+        grid.set(x + 1, y + 2, Alive);
+        grid.set(x + 2, y + 2, Alive);
+    }
+
+    #[test]
+    fn set_and_get() {
+        let mut grid = SparseGrid::<State>::new();
+        grid.set(-20, 35, Alive);
+        grid.set(3, 4, Alive);
+        assert_eq!(*grid.get(-20, 35), Alive);
+        assert_eq!(*grid.get(0, 0), State::Dead);
+        assert_eq!(grid.population(), 2);
+        assert_eq!(grid.bounding_box(), Some((-20, 4, 24, 32)));
+
+        grid.set(-20, 35, State::Dead);
+        assert_eq!(grid.population(), 1);
+        assert_eq!(grid.tiles.len(), 1);
+    }
+
+    #[test]
+    fn conversion() {
+        let mut frame = Frame::<State>::new_finite(20, 18);
+        *frame.get_mut(3, 17) = Alive;
+        *frame.get_mut(19, 0) = Alive;
+        let grid = SparseGrid::from_frame(&frame);
+        assert_eq!(grid.to_frame(0, 0, 20, 18), frame);
+        assert_eq!(grid.bounding_box(), Some((3, 0, 17, 18)));
+    }
+
+    #[test]
+    fn glider_never_wraps() {
+        let mut grid = SparseGrid::<State>::new();
+        glider(&mut grid, 0, 0);
+        let start = grid.bounding_frame();
+        for _ in 0..100 {
+            grid = grid.advance(&GameOfLife);
+        }
+        assert_eq!(grid.population(), 5);
+        assert_eq!(grid.bounding_box(), Some((25, 25, 3, 3)));
+        assert_eq!(grid.bounding_frame(), start);
+    }
+
+    #[test]
+    fn matches_frame() {
+        for rulestring in ["B36/S23", "B45/S34T"].iter() {
+            let rule = rulestring.parse::<LifeLike>().unwrap();
+            let mut frame = Frame::<State>::new_finite(60, 60);
+            let mut grid = SparseGrid::new();
+            let soup = scattered(16, 16, Boundary::Toroidal);
+            for (x, y, &state) in soup.enumerate_squares() {
+                *frame.get_mut(x + 22, y + 22) = state;
+                grid.set(x as i64 + 22, y as i64 + 22, state);
+            }
+            for _ in 0..10 {
+                frame = frame.advance(&rule);
+                grid = grid.advance(&rule);
+                assert_eq!(grid.to_frame(0, 0, 60, 60), frame);
+            }
+        }
+    }
+
+    #[test]
+    fn other_states() {
+        let brain = Generations::named("Brian's Brain").unwrap();
+        let mut grid = SparseGrid::new();
+        grid.set(15, 15, generations::State::Alive);
+        grid.set(16, 15, generations::State::Alive);
+        let next = grid.advance(&brain);
+        assert_eq!(*next.get(15, 15), generations::State::Dying(1));
+        assert_eq!(*next.get(16, 14), generations::State::Alive);
+        assert_eq!(next.population(), 6);
+    }
+}
@@ -1,25 +1,34 @@
-use super::Square;
+use super::{FiniteState, Rule, Square, MOORE};
 
 /// The state of a node in Rainbow Game of Life
-#[derive(Clone, Copy, Debug, PartialEq)]
+#[derive(Clone, Copy, Debug, PartialEq, Default)]
 pub enum State {
     /// an alive cell + it's color
     Alive(u8),
+    #[default]
     Dead,
 }
 
-impl Default for State {
-    fn default() -> State { State::Dead }
+impl FiniteState for State {
+    fn count() -> usize { 257 }
+
+    fn index(&self) -> usize {
+        match *self {
+            State::Dead => 0,
+            State::Alive(color) => 1 + color as usize,
+        }
+    }
+
+    fn from_index(index: usize) -> State {
+        if index == 0 { State::Dead } else { State::Alive((index - 1) as u8) }
+    }
 }
 
 impl<'a> Square<'a, State>
 where State: 'a {
     /// Return the number of nodes alive surrounding this number
     fn alive_count(&self) -> usize {
-        self.within_ortholinear(1)
-            .iter()
-            .filter(|e| { **e != State::Dead })
-            .count()
+        self.count_neighbors(&MOORE, |e| { *e != State::Dead })
     }
 }
 
@@ -37,7 +46,7 @@ pub fn rule(curr: Square<State>) -> State {
     } else {
         match curr.alive_count() {
             3 => {
-                let sum = curr.within_ortholinear(1).iter()
+                let sum = curr.neighbors(&MOORE)
                     .filter_map(|e| { match *e {
                         Alive(c) => Some(c as u16),
                         _ => None,
@@ -49,10 +58,23 @@ pub fn rule(curr: Square<State>) -> State {
     }
 }
 
+/// Rainbow Game of Life as a Rule, births average the colors of their parents
+#[derive(Clone, Copy, Debug, PartialEq, Default)]
+pub struct TwoColorLife;
+
+impl Rule for TwoColorLife {
+    type State = State;
+
+    fn name(&self) -> &str { "Rainbow Life" }
+    fn state_count(&self) -> Option<usize> { Some(257) }
+
+    fn step(&self, curr: Square<State>) -> State { rule(curr) }
+}
+
 #[cfg(test)]
 mod tests {
     use super::super::Frame;
-    use super::rule;
+    use super::{rule, TwoColorLife};
     use super::State;
     use super::State::Alive;
 
@@ -81,6 +103,16 @@ mod tests {
         assert_eq!(frame3, frame1);
     }
 
+    #[test]
+    fn as_rule() {
+        let mut frame = Frame::<State>::new(4, 4);
+        *frame.get_mut(1, 0) = Alive(7);
+        *frame.get_mut(1, 1) = Alive(7);
+        *frame.get_mut(1, 2) = Alive(7);
+
+        assert_eq!(frame.advance(&TwoColorLife), frame.next_frame(rule));
+    }
+
     #[test]
     fn glider() {
         // Create a glider as pictured and follow its transformations:
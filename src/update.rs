@@ -0,0 +1,240 @@
+use super::{Context, Frame, Rule};
+
+use rand::Rng;
+
+/// The order that the squares of a frame are updated in each generation.
+/// Randomness is drawn from the context, so runs with the same seed update
+/// squares in the same order
+#[derive(Clone, Debug, PartialEq, Default)]
+pub enum UpdateScheme {
+    /// Every square is updated at once from the previous generation
+    #[default]
+    Synchronous,
+    /// Every square is updated once, one at a time in a random order, and sees
+    /// the new states of the squares updated before it
+    RandomSequential,
+    /// Every square is updated once, one at a time in row major order, and
+    /// sees the new states of the squares updated before it
+    Sweep,
+    /// Each square is updated with the given probability and otherwise keeps
+    /// its state. The squares that are updated are updated at once. Updating
+    /// a frame panics if the probability isn't between 0 and 1
+    Alpha(f64),
+    /// The squares where x + y is even are updated at once, then the others are
+    /// updated at once seeing the new states of the first
+    Checkerboard,
+    /// The frame is tiled by blocks with the given side length. The squares at
+    /// the same position in every block are updated at once, one position at a
+    /// time in row major order. Blocks larger than the frame update every
+    /// square one at a time like Sweep
+    Blocks(usize),
+}
+
+impl<T> Frame<T>
+where T: Clone {
+    /// Advance the frame one generation in place with a rule, updating the
+    /// squares in the order given by an update scheme
+    pub fn update<R>(&mut self, rule: &R, scheme: &UpdateScheme, ctx: &Context)
+    where R: Rule<State = T> + ?Sized {
+        use self::UpdateScheme::*;
+        let (w, h) = (self.width(), self.height());
+        match *scheme {
+            Synchronous => *self = self.advance_with(rule, ctx),
+            RandomSequential => {
+                let mut order = (0..w * h).collect::<Vec<_>>();
+                ctx.generation_rng().shuffle(&mut order);
+                for i in order {
+                    self.update_one(rule, ctx, i);
+                }
+            },
+            Sweep => {
+                for i in 0..w * h {
+                    self.update_one(rule, ctx, i);
+                }
+            },
+            Alpha(alpha) => {
+                assert!((0.0..=1.0).contains(&alpha),
+                        "the probability {} isn't between 0 and 1", alpha);
+                let mut rng = ctx.generation_rng();
+                let chosen = (0..w * h).filter(|_| { rng.gen::<f64>() < alpha })
+                    .collect::<Vec<_>>();
+                self.update_at_once(rule, ctx, &chosen);
+            },
+            Checkerboard => {
+                for parity in 0..2 {
+                    let class = (0..w * h)
+                        .filter(|i| { (i % w + i / w) % 2 == parity })
+                        .collect::<Vec<_>>();
+                    self.update_at_once(rule, ctx, &class);
+                }
+            },
+            Blocks(n) => {
+                // positions past the frame have no squares to update
+                let n = n.clamp(1, w.max(h).max(1));
+                for (bx, by) in (0..n * n).map(|b| { (b % n, b / n) }) {
+                    let class = (by..h).step_by(n)
+                        .flat_map(|y| {
+                            (bx..w).step_by(n).map(move |x| { y * w + x })
+                        })
+                        .collect::<Vec<_>>();
+                    self.update_at_once(rule, ctx, &class);
+                }
+            },
+        }
+    }
+
+    /// return the next frame of the simulation advancing each square with a
+    /// rule, in the order given by an update scheme
+    pub fn advance_with_scheme<R>(&self, rule: &R, scheme: &UpdateScheme,
+                                  ctx: &Context) -> Frame<T>
+    where R: Rule<State = T> + ?Sized {
+        let mut next = self.clone();
+        next.update(rule, scheme, ctx);
+        next
+    }
+
+    /// Update the square at an index, seeing the current states of the others
+    fn update_one<R>(&mut self, rule: &R, ctx: &Context, i: usize)
+    where R: Rule<State = T> + ?Sized {
+        let (x, y) = (i % self.width(), i / self.width());
+        let value = self.fixed(x, y).unwrap_or_else(|| {
+            rule.step_with(self.square(x, y), ctx)
+        });
+        *self.get_mut(x, y) = value;
+    }
+
+    /// Update the squares at some indices in row major order at once
+    fn update_at_once<R>(&mut self, rule: &R, ctx: &Context, squares: &[usize])
+    where R: Rule<State = T> + ?Sized {
+        let w = self.width();
+        let values = squares.iter()
+            .map(|&i| {
+                let (x, y) = (i % w, i / w);
+                self.fixed(x, y).unwrap_or_else(|| {
+                    rule.step_with(self.square(x, y), ctx)
+                })
+            })
+            .collect::<Vec<_>>();
+        for (&i, value) in squares.iter().zip(values) {
+            *self.get_mut(i % w, i / w) = value;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::{Boundary, Context, Frame, Rule, Simulation, Square};
+    use super::super::rainbow_life::{self, RainbowLife};
+    use super::UpdateScheme::*;
+
+    /// Each square takes the value of the square to its left
+    struct Shift;
+
+    impl Rule for Shift {
+        type State = u8;
+
+        fn name(&self) -> &str { "shift" }
+
+        fn step(&self, curr: Square<u8>) -> u8 { *curr.get(-1, 0) }
+    }
+
+    /// Each square counts the number of times it was updated
+    struct Count;
+
+    impl Rule for Count {
+        type State = u8;
+
+        fn name(&self) -> &str { "count" }
+
+        fn step(&self, curr: Square<u8>) -> u8 { curr.get(0, 0) + 1 }
+    }
+
+    /// A row of squares numbered from 1
+    fn row(n: usize) -> Frame<u8> {
+        let mut frame = Frame::new_finite(n, 1);
+        for x in 0..n {
+            *frame.get_mut(x, 0) = x as u8 + 1;
+        }
+        frame
+    }
+
+    /// The values of a row
+    fn values(frame: &Frame<u8>) -> Vec<u8> {
+        frame.enumerate_squares().map(|(_, _, &v)| { v }).collect()
+    }
+
+    #[test]
+    fn orders() {
+        let ctx = Context::new(3);
+        let next = |scheme| {
+            values(&row(4).advance_with_scheme(&Shift, &scheme, &ctx))
+        };
+        assert_eq!(next(Synchronous), vec![0, 1, 2, 3]);
+        assert_eq!(next(Sweep), vec![0, 0, 0, 0]);
+        assert_eq!(next(Checkerboard), vec![0, 0, 2, 2]);
+        assert_eq!(next(Blocks(3)), vec![0, 0, 0, 3]);
+        assert_eq!(next(Alpha(0.0)), vec![1, 2, 3, 4]);
+        assert_eq!(next(Alpha(1.0)), vec![0, 1, 2, 3]);
+        assert_eq!(next(Blocks(1_000_000)), next(Sweep));
+    }
+
+    #[test]
+    #[should_panic(expected = "between 0 and 1")]
+    fn alpha_out_of_range() {
+        row(4).advance_with_scheme(&Shift, &Alpha(f64::NAN), &Context::new(3));
+    }
+
+    #[test]
+    fn every_square_once() {
+        let ctx = Context::new(9);
+        let frame = Frame::<u8>::new(7, 5);
+        for scheme in [Synchronous, RandomSequential, Sweep, Checkerboard,
+                       Blocks(2), Blocks(3), Blocks(10), Blocks(usize::MAX)]
+                           .iter() {
+            let next = frame.advance_with_scheme(&Count, scheme, &ctx);
+            assert!(values(&next).iter().all(|&v| { v == 1 }), "{:?}", scheme);
+        }
+
+        // about half of the squares are updated
+        let next = frame.advance_with_scheme(&Count, &Alpha(0.5), &ctx);
+        let updated = values(&next).iter().filter(|&&v| { v == 1 }).count();
+        assert!(updated > 5 && updated < 30);
+    }
+
+    #[test]
+    fn random_orders() {
+        let frame = row(8);
+        let order = |seed| {
+            let ctx = Context::new(seed);
+            values(&frame.advance_with_scheme(&Shift, &RandomSequential, &ctx))
+        };
+        assert_eq!(order(1), order(1));
+        assert!((2..10).any(|seed| { order(seed) != order(1) }));
+    }
+
+    #[test]
+    fn simulation_scheme() {
+        let mut frame = Frame::new(16, 16).with_boundary(Boundary::Toroidal);
+        for &(x, y) in [(1, 0), (2, 1), (0, 2), (1, 2), (2, 2)].iter() {
+            *frame.get_mut(x, y) = rainbow_life::State::Red;
+        }
+        let schemes = [Synchronous, RandomSequential, Alpha(0.7), Blocks(2)];
+        for scheme in schemes.iter() {
+            let run = |threads| {
+                let mut sim = Simulation::seeded(frame.clone(), 5)
+                    .with_scheme(scheme.clone());
+                for _ in 0..10 {
+                    sim.advance_parallel(threads, &RainbowLife);
+                }
+                sim.into_frame()
+            };
+            assert_eq!(run(1), run(3));
+        }
+
+        let mut sim = Simulation::seeded(frame.clone(), 5).with_scheme(Sweep);
+        sim.advance(&RainbowLife);
+        assert_eq!(*sim.frame(), frame.advance_with_scheme(
+            &RainbowLife, &Sweep, &Context::new(5)));
+        assert_eq!(*sim.scheme(), Sweep);
+    }
+}
@@ -0,0 +1,229 @@
+use super::{Context, Frame, Neighborhood, Rule, Square};
+
+/// A rule made of several rules that each advance the squares in their own
+/// zone. The zones are a frame of indices into the list of rules, which is
+/// looked up at the coordinate of each square, so zones smaller than the frame
+/// being advanced repeat according to their boundary condition
+#[derive(Clone, Debug, PartialEq)]
+pub struct Zoned<R> {
+    name: String,
+    rules: Vec<R>,
+    zones: Frame<usize>,
+}
+
+impl<R> Zoned<R>
+where R: Rule {
+    /// Create a rule where the square at (x, y) is advanced by the rule whose
+    /// index is at (x, y) in the zones. Panics if a zone has no rule
+    pub fn new(rules: Vec<R>, zones: Frame<usize>) -> Zoned<R> {
+        assert!(zones.enumerate_squares().all(|(_, _, &z)| { z < rules.len() }),
+                "every zone needs a rule");
+        let name = rules.iter().map(|r| { r.name() })
+            .collect::<Vec<_>>()
+            .join(" | ");
+        Zoned { name, rules, zones }
+    }
+
+    /// Create a rule for a frame of the given size where the zone of each
+    /// square is computed from its coordinate
+    pub fn from_fn<F>(rules: Vec<R>, width: usize, height: usize, zone: F)
+        -> Zoned<R>
+    where F: Fn(usize, usize) -> usize {
+        let mut zones = Frame::new(width, height);
+        for y in 0..height {
+            for x in 0..width {
+                *zones.get_mut(x, y) = zone(x, y);
+            }
+        }
+        Zoned::new(rules, zones)
+    }
+
+    /// Create a rule for a frame of the given size that is split into
+    /// vertical strips of equal width, one for each rule from left to right.
+    /// Rules with a parameter that changes a little from each one to the next
+    /// approximate a gradient of the parameter across the frame
+    pub fn strips(rules: Vec<R>, width: usize, height: usize) -> Zoned<R> {
+        let n = rules.len();
+        Zoned::from_fn(rules, width, height, |x, _| { x * n / width })
+    }
+
+    /// the rules of the zones
+    pub fn rules(&self) -> &[R] {
+        &self.rules
+    }
+
+    /// the index of the rule of every square
+    pub fn zones(&self) -> &Frame<usize> {
+        &self.zones
+    }
+
+    /// the index of the rule of the square at (x, y)
+    pub fn zone(&self, x: usize, y: usize) -> usize {
+        *self.zones.at(x as isize, y as isize)
+    }
+
+    /// change the rule of the square at (x, y) in the zones
+    pub fn set_zone(&mut self, x: usize, y: usize, zone: usize) {
+        assert!(zone < self.rules.len(), "every zone needs a rule");
+        *self.zones.get_mut(x, y) = zone;
+    }
+
+    /// The rule that advances a square
+    fn rule_at(&self, curr: &Square<R::State>) -> &R {
+        let (x, y) = curr.coordinate();
+        &self.rules[self.zone(x, y)]
+    }
+}
+
+impl<R> Rule for Zoned<R>
+where R: Rule {
+    type State = R::State;
+
+    fn name(&self) -> &str { &self.name }
+
+    fn neighborhood(&self) -> Neighborhood {
+        Neighborhood::covering(self.rules.iter().map(|r| { r.neighborhood() }))
+    }
+
+    fn state_count(&self) -> Option<usize> {
+        self.rules.iter()
+            .map(|r| { r.state_count() })
+            .try_fold(0, |acc, n| { Some(acc.max(n?)) })
+    }
+
+    fn is_deterministic(&self) -> bool {
+        self.rules.iter().all(|r| { r.is_deterministic() })
+    }
+
+    fn is_uniform(&self) -> bool {
+        self.rules.len() == 1 && self.rules[0].is_uniform()
+    }
+
+    fn is_stationary(&self) -> bool {
+        self.rules.iter().all(|r| { r.is_stationary() })
+    }
+
+    fn step(&self, curr: Square<R::State>) -> R::State {
+        self.rule_at(&curr).step(curr)
+    }
+
+    fn step_with(&self, curr: Square<R::State>, ctx: &Context) -> R::State {
+        self.rule_at(&curr).step_with(curr, ctx)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::{Boundary, Context, Frame, Rule, Simulation, Square};
+    use super::super::compiled::{CompileError, Compiled};
+    use super::super::frame::tests::{pattern, scattered};
+    use super::super::game_of_life::{Isotropic, LifeLike, State};
+    use super::super::rainbow_life::{self, RainbowLife};
+    use super::super::sparse::SparseGrid;
+    use super::Zoned;
+
+    /// Check that every square is advanced by the rule of its zone
+    fn check_zones<R>(zoned: &Zoned<R>, frame: &Frame<R::State>, ctx: &Context)
+    where R: Rule,
+          R::State: Clone + PartialEq + ::std::fmt::Debug {
+        let next = frame.advance_with(zoned, ctx);
+        let each = zoned.rules().iter().map(|r| { frame.advance_with(r, ctx) })
+            .collect::<Vec<_>>();
+        for (x, y, state) in next.enumerate_squares() {
+            assert_eq!(state, each[zoned.zone(x, y)].get(x, y));
+        }
+    }
+
+    #[test]
+    fn halves() {
+        let life = "B3/S23".parse::<LifeLike>().unwrap();
+        let highlife = "B36/S23".parse::<LifeLike>().unwrap();
+        let zoned = Zoned::strips(vec![life, highlife], 20, 12);
+        assert_eq!(zoned.name(), "B3/S23 | B36/S23");
+        assert_eq!(zoned.zone(9, 4), 0);
+        assert_eq!(zoned.zone(10, 4), 1);
+        assert!(zoned.is_deterministic() && !zoned.is_uniform());
+
+        let mut frame = scattered(20, 12, Boundary::Toroidal);
+        for _ in 0..5 {
+            check_zones(&zoned, &frame, &Context::default());
+            frame = frame.advance(&zoned);
+        }
+    }
+
+    #[test]
+    fn different_kinds_of_rules() {
+        let rules: Vec<Box<dyn Rule<State = State> + Sync>> = vec![
+            Box::new("B3/S23".parse::<LifeLike>().unwrap()),
+            Box::new("B2-a/S12".parse::<Isotropic>().unwrap()),
+        ];
+        // a checkerboard of 4 by 4 blocks repeated across the frame
+        let mut zones = Frame::new(8, 8);
+        for y in 0..8 {
+            for x in 0..8 {
+                *zones.get_mut(x, y) = (x / 4 + y / 4) % 2;
+            }
+        }
+        let zoned = Zoned::new(rules, zones);
+        assert_eq!(zoned.zone(13, 2), 1);
+        assert_eq!(zoned.zone(13, 6), 0);
+
+        let frame = scattered(24, 16, Boundary::Toroidal);
+        check_zones(&zoned, &frame, &Context::default());
+        assert_eq!(frame.advance_parallel(3, &zoned), frame.advance(&zoned));
+    }
+
+    /// Squares never change
+    struct Still;
+
+    impl Rule for Still {
+        type State = rainbow_life::State;
+
+        fn name(&self) -> &str { "still" }
+
+        fn step(&self, curr: Square<rainbow_life::State>)
+            -> rainbow_life::State {
+            *curr.get(0, 0)
+        }
+    }
+
+    #[test]
+    fn bordering_regions() {
+        use super::super::rainbow_life::State::{Red, Green, Blue};
+        let rules: Vec<Box<dyn Rule<State = rainbow_life::State> + Sync>> =
+            vec![Box::new(RainbowLife), Box::new(Still)];
+        let mut zoned = Zoned::from_fn(rules, 16, 16,
+                                       |x, _| { (x >= 12) as usize });
+        assert!(!zoned.is_deterministic());
+        zoned.set_zone(3, 3, 1);
+
+        let frame = pattern(16, 16, Boundary::Toroidal, &[Red, Green, Blue]);
+        let ctx = Context::new(8);
+        check_zones(&zoned, &frame, &ctx);
+
+        let mut sim = Simulation::seeded(frame.clone(), 8);
+        for _ in 0..10 {
+            sim.advance(&zoned);
+        }
+        for (x, y, state) in frame.enumerate_squares() {
+            if zoned.zone(x, y) == 1 {
+                assert_eq!(sim.frame().get(x, y), state);
+            }
+        }
+    }
+
+    #[test]
+    fn nonuniform() {
+        let life = "B3/S23".parse::<LifeLike>().unwrap();
+        let zoned = Zoned::strips(vec![life.clone(), life], 4, 4);
+        assert_eq!(Compiled::new(&zoned).err(), Some(CompileError::Nonuniform));
+    }
+
+    #[test]
+    #[should_panic]
+    fn nonuniform_sparse() {
+        let life = "B3/S23".parse::<LifeLike>().unwrap();
+        let zoned = Zoned::strips(vec![life.clone(), life], 4, 4);
+        SparseGrid::<State>::new().advance(&zoned);
+    }
+}